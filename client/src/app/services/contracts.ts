@@ -107,12 +107,10 @@ export const addBook = async (
  * @returns A promise that resolves when the transaction is complete
  */
 export const borrowBook = async (tokenId: string): Promise<string> => {
-  const { injectiveAddress } = useWalletStore.getState();
-  
+  // The contract lends the book to the transaction sender
   const contractMsg = {
     borrow_book: {
-      token_id: tokenId,
-      borrower: injectiveAddress
+      token_id: tokenId
     }
   };
   
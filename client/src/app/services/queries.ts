@@ -8,6 +8,15 @@ export interface Book {
   title: string;
   author: string;
   url: string;
+  co_authors: string[];
+  isbn: string | null;
+  publication_year: number | null;
+  genres: string[];
+  publisher: string | null;
+  language: string | null;
+  page_count: number | null;
+  description: string | null;
+  image: string | null;
   owner: string;
 }
 
@@ -16,6 +25,19 @@ export interface BookWithId {
   book: Book;
 }
 
+// Mirrors `BookResponse` in the contract schema, returned by `get_book`
+export interface BookResponse {
+  token_id: string;
+  book: Book;
+  borrower: string | null;
+}
+
+// Mirrors `BooksResponse`, a page of books returned by the list queries
+export interface BooksResponse {
+  books: BookResponse[];
+  next_start_after: string | null;
+}
+
 // Initialize the Wasm API client 
 const wasmClient = new ChainGrpcWasmApi(ENDPOINTS.grpc);
 
@@ -26,6 +48,25 @@ const decodeResponseData = (data: Uint8Array): any => {
   return JSON.parse(jsonStr);
 };
 
+/**
+ * Fetch every page of a book list query, following the next-page cursor
+ */
+const fetchAllBooks = async (queryName: string, args: Record<string, unknown> = {}): Promise<BookWithId[]> => {
+  const books: BookWithId[] = [];
+  let startAfter: string | null = null;
+
+  do {
+    const queryMsg = { [queryName]: { ...args, start_after: startAfter } };
+    const response = await wasmClient.fetchSmartContractState(CONTRACT_ADDRESS, toBase64(queryMsg));
+    const page = decodeResponseData(response.data) as BooksResponse;
+
+    books.push(...page.books.map(({ token_id, book }) => ({ id: token_id, book })));
+    startAfter = page.next_start_after;
+  } while (startAfter);
+
+  return books;
+};
+
 /**
  * Get all books borrowed by the current user
  * This function requires the user to be connected with their wallet
@@ -38,15 +79,7 @@ export const getMyBorrowedBooks = async (): Promise<BookWithId[]> => {
   }
   
   try {
-    const queryMsg = { get_my_borrowed_books: { borrower: injectiveAddress } };
-    const base64queryMsg = toBase64(queryMsg);
-    
-    const response = await wasmClient.fetchSmartContractState(CONTRACT_ADDRESS, base64queryMsg);
-    
-    return (decodeResponseData(response.data) as [string, Book][]).map(([id, book]) => ({
-      id,
-      book
-    }));
+    return await fetchAllBooks("get_my_borrowed_books", { borrower: injectiveAddress });
   } catch (error) {
     console.error("Error fetching borrowed books:", error);
     return [];
@@ -58,15 +91,7 @@ export const getMyBorrowedBooks = async (): Promise<BookWithId[]> => {
  */
 export const getAvailableBooks = async (): Promise<BookWithId[]> => {
   try {
-    const queryMsg = { get_available_books: {} };
-    const base64queryMsg = toBase64(queryMsg);
-    
-    const response = await wasmClient.fetchSmartContractState(CONTRACT_ADDRESS, base64queryMsg);
-    
-    return (decodeResponseData(response.data) as [string, Book][]).map(([id, book]) => ({
-      id,
-      book
-    }));
+    return await fetchAllBooks("get_available_books");
   } catch (error) {
     console.error("Error fetching available books:", error);
     return [];
@@ -41,19 +41,25 @@ for (( i=0; i<$BOOK_COUNT; i++ )); do
     
     echo "Processing book $ID: $TITLE by $AUTHOR"
     
-    # Create JSON payload for the contract call
-    ADD_BOOK_MSG=$(cat <<EOF
-    {
-        "add_book": {
-            "token_id": "$ID",
-            "title": "$TITLE",
-            "author": "$AUTHOR",
-            "url": "$URL",
-            "owner": "$OWNER_ADDRESS"
+    # Create JSON payload for the contract call, catalogue fields missing from a book are left unset
+    ADD_BOOK_MSG=$(echo "$BOOK" | jq -c --arg owner "$OWNER_ADDRESS" '{
+        add_book: {
+            token_id: (.id | tostring),
+            title: .title,
+            author: .author,
+            url: .url,
+            owner: $owner,
+            co_authors: (.co_authors // []),
+            isbn: .isbn,
+            publication_year: .year,
+            genres: ([.genre] | map(select(. != null))),
+            publisher: .publisher,
+            language: .language,
+            page_count: .page_count,
+            description: .description,
+            image: .image
         }
-    }
-EOF
-    )
+    }')
     
     # Execute contract call to add the book
     echo "Adding book to the smart contract..."
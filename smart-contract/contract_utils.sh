@@ -88,9 +88,15 @@ add_book() {
 
 borrow_book() {
     local token_id="$1"
-    local borrower="${2:-$WALLET_ADDR}"
+    local borrower="$2"
     
-    execute_contract '{"borrow_book": {"token_id": "'$token_id'", "borrower": "'$borrower'"}}'
+    # Without a borrower the book is lent to the sending wallet,
+    # otherwise the borrower must have approved the wallet as a borrow delegate
+    if [ -z "$borrower" ]; then
+        execute_contract '{"borrow_book": {"token_id": "'$token_id'"}}'
+    else
+        execute_contract '{"borrow_book_for": {"token_id": "'$token_id'", "borrower": "'$borrower'"}}'
+    fi
 }
 
 return_book() {
@@ -112,7 +118,7 @@ if [ $# -eq 0 ]; then
     echo "  get_my_borrowed_books       - Get books borrowed by your wallet"
     echo "  get_available_books         - Get all books available for borrowing"
     echo "  add_book <id> <title> <author> <book_url> [owner] - Add a new book"
-    echo "  borrow_book <token_id> [borrower]   - Borrow a book"
+    echo "  borrow_book <token_id> [borrower]   - Borrow a book, for an approving borrower if given"
     echo "  return_book <token_id>      - Return a book"
     exit 1
 fi
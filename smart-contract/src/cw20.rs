@@ -0,0 +1,104 @@
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, QuerierWrapper, StdResult, Uint128, WasmMsg,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// CW20 payments, message shapes follow the cw20 spec so that any compliant token contract
+// can pay through `send`. Inside the library, CW20 amounts are handled as coins whose denom
+// is `cw20:<token contract address>`, next to native denoms.
+
+const CW20_DENOM_PREFIX: &str = "cw20:";
+
+/// Cw20ReceiveMsg should be de/serialized under `Receive()` variant in a ExecuteMsg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw20ReceiveMsg {
+    pub sender: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+// The part of the cw20 execute interface used to pay out tokens held by the contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum Cw20ExecuteMsg {
+    Transfer { recipient: String, amount: Uint128 },
+}
+
+// The part of the cw20 query interface used to check the tokens held by the contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum Cw20QueryMsg {
+    Balance { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct BalanceResponse {
+    balance: Uint128,
+}
+
+/// Balance of an address in a native or CW20 denom
+pub fn query_balance(querier: &QuerierWrapper, address: &Addr, denom: &str) -> StdResult<Uint128> {
+    match cw20_contract(denom) {
+        Some(contract) => {
+            let response: BalanceResponse = querier.query_wasm_smart(
+                contract,
+                &Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )?;
+            Ok(response.balance)
+        }
+        None => Ok(querier
+            .query_all_balances(address)?
+            .into_iter()
+            .find(|coin| coin.denom == denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default()),
+    }
+}
+
+/// Denom under which the tokens of a CW20 contract are accounted
+pub fn cw20_denom(contract: &Addr) -> String {
+    format!("{}{}", CW20_DENOM_PREFIX, contract)
+}
+
+/// Address of the CW20 contract behind a denom, `None` for native denoms
+pub fn cw20_contract(denom: &str) -> Option<&str> {
+    denom.strip_prefix(CW20_DENOM_PREFIX)
+}
+
+/// Messages sending coins to a recipient: a bank send for the native denoms and
+/// a cw20 transfer for each CW20 denom. Zero amounts are left out.
+pub fn payout_msgs(recipient: &Addr, coins: Vec<Coin>) -> StdResult<Vec<CosmosMsg>> {
+    let mut native = vec![];
+    let mut msgs = vec![];
+    for coin in coins.into_iter().filter(|coin| !coin.amount.is_zero()) {
+        match cw20_contract(&coin.denom) {
+            Some(contract) => msgs.push(
+                WasmMsg::Execute {
+                    contract_addr: contract.to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: recipient.to_string(),
+                        amount: coin.amount,
+                    })?,
+                    funds: vec![],
+                }
+                .into(),
+            ),
+            None => native.push(coin),
+        }
+    }
+    if !native.is_empty() {
+        msgs.insert(
+            0,
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: native,
+            }
+            .into(),
+        );
+    }
+    Ok(msgs)
+}
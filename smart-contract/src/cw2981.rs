@@ -0,0 +1,97 @@
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::{Deps, StdResult, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::tokens;
+
+// CW2981 royalties, query messages and responses follow the cw2981-royalties spec so that
+// marketplaces can look up the royalty owed to a book's author on secondary sales.
+// Royalty terms are read from the token metadata set at mint time.
+
+/// Queries of the CW2981 extension, sent as `{"extension": {"msg": ...}}`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw2981QueryMsg {
+    /// Should be called on sale to see if royalties are owed
+    /// by the marketplace selling the NFT, if CheckRoyalties
+    /// returns true
+    #[returns(RoyaltiesInfoResponse)]
+    RoyaltyInfo {
+        token_id: String,
+        // the denom of this sale must also be the denom returned by RoyaltiesInfoResponse
+        sale_price: Uint128,
+    },
+    /// Called against contract to determine if this NFT
+    /// implements royalties. Should return a boolean as part of
+    /// CheckRoyaltiesResponse - default can simply be true
+    /// if royalties are implemented at token level
+    /// (i.e. always check on sale)
+    #[returns(CheckRoyaltiesResponse)]
+    CheckRoyalties {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoyaltiesInfoResponse {
+    pub address: String,
+    // Note that this must be the same denom as that passed in to RoyaltyInfo
+    // rounding up or down is at the discretion of the implementer
+    pub royalty_amount: Uint128,
+}
+
+/// Shows if the contract implements royalties
+/// if royalty_payments is true, marketplaces should pay them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CheckRoyaltiesResponse {
+    pub royalty_payments: bool,
+}
+
+/// Response of a CW2981 extension query, serialized as the response of the query sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum Cw2981Response {
+    RoyaltyInfo(RoyaltiesInfoResponse),
+    CheckRoyalties(CheckRoyaltiesResponse),
+}
+
+/// Query handler for the CW2981 extension queries
+pub fn query_royalties(deps: Deps, msg: Cw2981QueryMsg) -> StdResult<Cw2981Response> {
+    match msg {
+        Cw2981QueryMsg::RoyaltyInfo {
+            token_id,
+            sale_price,
+        } => Ok(Cw2981Response::RoyaltyInfo(query_royalties_info(
+            deps, token_id, sale_price,
+        )?)),
+        Cw2981QueryMsg::CheckRoyalties {} => Ok(Cw2981Response::CheckRoyalties(check_royalties())),
+    }
+}
+
+/// Royalty owed on a sale of a book, nothing if it was minted without royalties
+pub fn query_royalties_info(
+    deps: Deps,
+    token_id: String,
+    sale_price: Uint128,
+) -> StdResult<RoyaltiesInfoResponse> {
+    let metadata = tokens().load(deps.storage, &token_id)?.metadata;
+
+    // Nothing is owed without an address to pay it to
+    let response = match (metadata.royalty_percentage, metadata.royalty_payment_address) {
+        (Some(percentage), Some(address)) => RoyaltiesInfoResponse {
+            address,
+            royalty_amount: sale_price.multiply_ratio(percentage, 100u128),
+        },
+        _ => RoyaltiesInfoResponse {
+            address: String::new(),
+            royalty_amount: Uint128::zero(),
+        },
+    };
+    Ok(response)
+}
+
+/// Royalties are set per book, marketplaces should always check them on sale
+pub fn check_royalties() -> CheckRoyaltiesResponse {
+    CheckRoyaltiesResponse {
+        royalty_payments: true,
+    }
+}
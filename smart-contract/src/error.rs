@@ -1,11 +1,17 @@
 use cosmwasm_std::StdError;
+use cw_utils::PaymentError;
 use thiserror::Error;
 
+use crate::ownership::OwnershipError;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Ownership(#[from] OwnershipError),
+
     #[error("Unauthorized")]
     Unauthorized {},
     
@@ -23,4 +29,124 @@ pub enum ContractError {
     
     #[error("Operator not found")]
     OperatorNotFound {},
+
+    #[error("Contract is paused for {category}")]
+    Paused { category: String },
+
+    #[error("{0}")]
+    Semver(#[from] semver::Error),
+
+    #[error("Cannot migrate from a different contract: {contract}")]
+    ForeignContract { contract: String },
+
+    #[error("Cannot migrate from version {stored} to older version {new}")]
+    Downgrade { stored: String, new: String },
+
+    #[error("Migration requires the `{field}` field")]
+    MissingMigrateField { field: String },
+
+    #[error("Book not found")]
+    BookNotFound {},
+
+    #[error("Book is on loan")]
+    BookOnLoan {},
+
+    #[error("Book is already borrowed")]
+    AlreadyBorrowed {},
+
+    #[error("Book is not available for lending")]
+    LendingClosed {},
+
+    #[error("Book can only be borrowed by members")]
+    MembersOnly {},
+
+    #[error("A borrow request for this book is already pending")]
+    AlreadyRequested {},
+
+    #[error("Borrow request not found")]
+    RequestNotFound {},
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Insufficient funds: required {required}, sent {sent}")]
+    InsufficientFunds { required: String, sent: String },
+
+    #[error("Wrong denom: expected {expected}, sent {sent}")]
+    WrongDenom { expected: String, sent: String },
+
+    #[error("Sent more than the price: required {required}, sent {sent}")]
+    ExcessFunds { required: String, sent: String },
+
+    #[error("Loan has no deposit")]
+    NoDeposit {},
+
+    #[error("Deposit can only be claimed once the loan is overdue past the grace period")]
+    DepositNotClaimable {},
+
+    #[error("Loan periods, the deposit grace period and late fee periods must count the same unit as the default loan period")]
+    MismatchedDurations {},
+
+    #[error("Late fee must be charged per a non-zero period")]
+    InvalidLateFee {},
+
+    #[error("Borrower has unpaid late fees")]
+    OutstandingFees {},
+
+    #[error("No late fees are owed")]
+    NoOutstandingFees {},
+
+    #[error("Loan is overdue, return the book to settle its late fee")]
+    LoanOverdue {},
+
+    #[error("CW20 token {address} is not accepted as payment")]
+    Cw20NotAccepted { address: String },
+
+    #[error("Treasury only holds {available}")]
+    InsufficientTreasury { available: String },
+
+    #[error("Contract balance {balance} does not cover the {required} owed to the treasury and borrowers")]
+    TreasuryImbalance { balance: String, required: String },
+
+    #[error("Owner, author and platform shares must sum to 10000 basis points")]
+    InvalidSplit {},
+
+    #[error("Platform share can't be lower than the platform fee of {min} basis points")]
+    PlatformShareTooLow { min: u16 },
+
+    #[error("Royalty percentage must be between 0 and 100")]
+    InvalidRoyaltyPercentage {},
+
+    #[error("Royalty percentage and royalty payment address must be set together")]
+    IncompleteRoyalty {},
+
+    #[error("ISBN must have 10 or 13 digits, the last digit of an ISBN-10 may be X")]
+    InvalidIsbn {},
+
+    #[error("Fee must be between 0 and 10000 basis points")]
+    InvalidFee {},
+
+    #[error("Book {token_id} is not on loan")]
+    LoanNotFound { token_id: String },
+
+    #[error("Loan has already been renewed the maximum of {max} times")]
+    MaxRenewalsReached { max: u32 },
+
+    #[error("Book is reserved for another member")]
+    BookReserved {},
+
+    #[error("Book is available, borrow it instead of placing a hold")]
+    BookAvailable {},
+
+    #[error("Already waiting for this book")]
+    AlreadyOnHold {},
+
+    #[error("Hold not found")]
+    HoldNotFound {},
+
+    #[error("At most {max} members can wait for a book")]
+    HoldQueueFull { max: usize },
+
+    #[error("Unknown role policy action: {action}")]
+    UnknownAction { action: String },
 }
@@ -1,22 +1,37 @@
+pub mod cw20;
+pub mod cw2981;
 pub mod error;
+pub mod migrations;
 pub mod msg;
+pub mod ownership;
 pub mod state;
 
+#[cfg(test)]
+mod multi_tests;
+
 use cosmwasm_std::{
-    entry_point, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response,
-    StdResult, Order, WasmMsg,
+    entry_point, from_json, to_json_binary, Addr, Binary, BlockInfo, Coin, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo,
+    Response, StdResult, Storage, Order, Uint128,
 };
-use cw_storage_plus::Bound;
+use cw_storage_plus::{Bound, Map};
+use cw_utils::{nonpayable, Duration, PaymentError};
 use cw2::set_contract_version;
 use cw721::{
-    AllNftInfoResponse, Approval, ApprovalResponse, ApprovalsResponse, ContractInfoResponse, 
+    AllNftInfoResponse, Approval, ApprovalResponse, ApprovalsResponse, ContractInfoResponse, Cw721ReceiveMsg,
     Expiration, NftInfoResponse, NumTokensResponse, OperatorsResponse, OwnerOfResponse, TokensResponse,
 };
 
+use crate::cw20::{cw20_contract, cw20_denom, payout_msgs, query_balance, Cw20ReceiveMsg};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg, RevenueSplitMsg};
+use crate::msg::{
+    AcceptedCw20Response, ActionPolicy, BookResponse, BooksResponse, BorrowRequestResponse, BorrowRequestsResponse, EscrowResponse, HoldsResponse,
+    OutstandingFeeResponse, OutstandingFeesResponse, LoanResponse, LoansResponse, MinterResponse, PauseStatusResponse, RoleHoldersResponse,
+    RolePolicyResponse, RolesResponse, RoyaltyInfoResponse, TreasuryResponse,
+};
 use crate::state::{
-    Approval as StateApproval, CONTRACT_INFO, Metadata, NUM_TOKENS, OPERATORS, TokenInfo, TOKENS, BORROWERS, BOOKS, Book,
+    Approval as StateApproval, CONTRACT_INFO, Metadata, MINTER, NUM_TOKENS, OPERATORS, TokenInfo, tokens, loans, Book,
+    borrow_requests, held_for_borrowers, BorrowRequest, RevenueSplit, FeeConfig, ACCEPTED_CW20, TREASURY, Hold, LateFee, OutstandingFee, ESCROWED_DEPOSITS, FEE_CONFIG, OUTSTANDING_FEES, PENDING_PAYMENTS, LendingPolicy, Loan, LoanConfig, BORROW_DELEGATES, BORROW_REQUEST_SEQ, PauseCategory, Reservation, Role, HOLDS, MAX_HOLDS, LOAN_CONFIG, RESERVATIONS, PAUSED, POLICY_ACTIONS, ROLES, ROLE_POLICY, STAFF_ACTIONS,
 };
 
 // Version information
@@ -27,29 +42,74 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     // Initialize contract info
-    let info = ContractInfoResponse {
+    let contract_info = ContractInfoResponse {
         name: msg.name,
         symbol: msg.symbol,
     };
-    CONTRACT_INFO.save(deps.storage, &info)?;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
 
     // Initialize token count
     NUM_TOKENS.save(deps.storage, &0)?;
 
-    // Set minter if provided
+    // Store the minter, the only address allowed to create tokens
     let minter = deps.api.addr_validate(&msg.minter)?;
+    MINTER.save(deps.storage, &minter)?;
+
+    // Set the contract owner, defaulting to the instantiator
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender,
+    };
+    ownership::initialize_owner(deps.storage, &owner)?;
+
+    // Default role policy: staff can add books and return loans on behalf of members
+    for action in STAFF_ACTIONS {
+        ROLE_POLICY.save(deps.storage, action, &vec![Role::Admin, Role::Librarian])?;
+    }
+
+    // Lending configuration
+    let mut loan_config = LoanConfig::default();
+    if let Some(period) = msg.default_loan_period {
+        loan_config.default_loan_period = period;
+    }
+    if let Some(max_renewals) = msg.max_renewals {
+        loan_config.max_renewals = max_renewals;
+    }
+    if let Some(pickup_window) = msg.pickup_window {
+        loan_config.pickup_window = pickup_window;
+    }
+    if let Some(grace_period) = msg.deposit_grace_period {
+        loan_config.deposit_grace_period = grace_period;
+    }
+    if let Some(transfer_loans) = msg.transfer_loans {
+        loan_config.transfer_loans = transfer_loans;
+    }
+    assert_same_unit(&loan_config.default_loan_period, &loan_config.deposit_grace_period)?;
+    LOAN_CONFIG.save(deps.storage, &loan_config)?;
+
+    // Platform fee on borrow fees
+    let fee_config = FeeConfig {
+        platform_fee_bps: msg.platform_fee_bps.unwrap_or(0),
+        late_fee: msg.late_fee,
+    };
+    if fee_config.platform_fee_bps > 10_000 {
+        return Err(ContractError::InvalidFee {});
+    }
+    validate_late_fee(deps.storage, fee_config.late_fee.as_ref())?;
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
 
     Ok(Response::new()
         .add_attribute("method", "instantiate")
         .add_attribute("contract_name", CONTRACT_NAME)
         .add_attribute("contract_version", CONTRACT_VERSION)
-        .add_attribute("minter", minter))
+        .add_attribute("minter", minter)
+        .add_attribute("owner", owner))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -59,6 +119,16 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // Reject state-changing messages while their category is paused
+    if let Some(category) = pause_category(&msg) {
+        assert_not_paused(deps.as_ref(), category)?;
+    }
+
+    // Enforce the configured role policy before dispatching
+    if let Some(action) = policy_action(&msg) {
+        assert_role_policy(deps.as_ref(), &info.sender, action)?;
+    }
+
     match msg {
         // CW721 standard messages
         ExecuteMsg::TransferNft {
@@ -88,6 +158,34 @@ pub fn execute(
             metadata,
         } => execute_mint(deps, env, info, token_id, owner, metadata),
         ExecuteMsg::Burn { token_id } => execute_burn(deps, env, info, token_id),
+        ExecuteMsg::UpdateMinter { new_minter } => execute_update_minter(deps, info, new_minter),
+        ExecuteMsg::UpdateOwnership(action) => execute_update_ownership(deps, env, info, action),
+        ExecuteMsg::GrantRole { role, address } => execute_grant_role(deps, info, role, address),
+        ExecuteMsg::RevokeRole { role, address } => execute_revoke_role(deps, info, role, address),
+        ExecuteMsg::SetRolePolicy { action, roles } => {
+            execute_set_role_policy(deps, info, action, roles)
+        }
+        ExecuteMsg::Pause { categories } => execute_pause(deps, info, categories),
+        ExecuteMsg::Unpause { categories } => execute_unpause(deps, info, categories),
+        ExecuteMsg::UpdateLoanConfig {
+            default_loan_period,
+            max_renewals,
+            pickup_window,
+            deposit_grace_period,
+            transfer_loans,
+        } => execute_update_loan_config(
+            deps,
+            info,
+            default_loan_period,
+            max_renewals,
+            pickup_window,
+            deposit_grace_period,
+            transfer_loans,
+        ),
+        ExecuteMsg::SetLoanPeriod {
+            token_id,
+            loan_period,
+        } => execute_set_loan_period(deps, info, token_id, loan_period),
 
         // Legacy messages for backwards compatibility
         ExecuteMsg::AddBook {
@@ -96,13 +194,135 @@ pub fn execute(
             author,
             url,
             owner,
+            co_authors,
+            isbn,
+            publication_year,
+            genres,
+            publisher,
+            language,
+            page_count,
+            description,
+            image,
         } => {
-            execute_add_book(deps, info, token_id, title, author, url, owner)
+            let metadata = Metadata {
+                title,
+                author,
+                url,
+                co_authors,
+                isbn,
+                publication_year,
+                genres,
+                publisher,
+                language,
+                page_count,
+                description,
+                image,
+                ..Default::default()
+            };
+            execute_add_book(deps, info, token_id, metadata, owner)
+        }
+        ExecuteMsg::BorrowBook { token_id } => execute_borrow_book(deps, env, info, token_id),
+        ExecuteMsg::BorrowBookFor { token_id, borrower } => {
+            execute_borrow_book_for(deps, env, info, token_id, borrower)
+        }
+        ExecuteMsg::ApproveBorrowDelegate { delegate, expires } => {
+            execute_approve_borrow_delegate(deps, env, info, delegate, expires)
+        }
+        ExecuteMsg::RevokeBorrowDelegate { delegate } => {
+            execute_revoke_borrow_delegate(deps, info, delegate)
+        }
+        ExecuteMsg::ReturnBook { token_id } => execute_return_book(deps, env, info, token_id),
+        ExecuteMsg::RenewLoan { token_id } => execute_renew_loan(deps, env, info, token_id),
+        ExecuteMsg::SetLendingPolicy { token_id, policy } => {
+            execute_set_lending_policy(deps, info, token_id, policy)
+        }
+        ExecuteMsg::SetBorrowPrice { token_id, price } => {
+            execute_set_borrow_price(deps, info, token_id, price)
+        }
+        ExecuteMsg::SetDeposit { token_id, deposit } => {
+            execute_set_deposit(deps, info, token_id, deposit)
+        }
+        ExecuteMsg::ClaimDeposit { token_id } => execute_claim_deposit(deps, env, info, token_id),
+        ExecuteMsg::SetLateFee { token_id, late_fee } => {
+            execute_set_late_fee(deps, info, token_id, late_fee)
+        }
+        ExecuteMsg::PayFees {} => execute_pay_fees(deps, info),
+        ExecuteMsg::TipAuthor { token_id } => execute_tip_author(deps, info, token_id),
+        ExecuteMsg::SetRevenueSplit { token_id, split } => {
+            execute_set_revenue_split(deps, info, token_id, split)
+        }
+        ExecuteMsg::Receive(wrapper) => execute_receive(deps, env, info, wrapper),
+        ExecuteMsg::WithdrawTreasury { amount, recipient } => {
+            execute_withdraw_treasury(deps, env, info, amount, recipient)
         }
-        ExecuteMsg::BorrowBook { token_id, borrower } => {
-            execute_borrow_book(deps, info, token_id, borrower)
+        ExecuteMsg::UpdateAcceptedCw20 { add, remove } => {
+            execute_update_accepted_cw20(deps, info, add, remove)
         }
-        ExecuteMsg::ReturnBook { token_id } => execute_return_book(deps, info, token_id),
+        ExecuteMsg::UpdateFeeConfig {
+            platform_fee_bps,
+            late_fee,
+        } => execute_update_fee_config(deps, info, platform_fee_bps, late_fee),
+        ExecuteMsg::AcceptBorrowRequest { request_id } => {
+            execute_accept_borrow_request(deps, env, info, request_id)
+        }
+        ExecuteMsg::RejectBorrowRequest { request_id } => {
+            execute_reject_borrow_request(deps, info, request_id)
+        }
+        ExecuteMsg::CancelBorrowRequest { request_id } => {
+            execute_cancel_borrow_request(deps, info, request_id)
+        }
+        ExecuteMsg::PlaceHold { token_id } => execute_place_hold(deps, env, info, token_id),
+        ExecuteMsg::CancelHold { token_id } => execute_cancel_hold(deps, env, info, token_id),
+    }
+}
+
+// Pause category of a message, admin messages can never be paused
+fn pause_category(msg: &ExecuteMsg) -> Option<PauseCategory> {
+    match msg {
+        // Revoke and RevokeAll stay open, owners must be able to withdraw approvals during a pause
+        ExecuteMsg::TransferNft { .. }
+        | ExecuteMsg::SendNft { .. }
+        | ExecuteMsg::Approve { .. }
+        | ExecuteMsg::ApproveAll { .. } => Some(PauseCategory::Transfers),
+        ExecuteMsg::Mint { .. } | ExecuteMsg::Burn { .. } | ExecuteMsg::AddBook { .. } => {
+            Some(PauseCategory::Mints)
+        }
+        ExecuteMsg::BorrowBook { .. }
+        | ExecuteMsg::BorrowBookFor { .. }
+        | ExecuteMsg::RenewLoan { .. }
+        | ExecuteMsg::AcceptBorrowRequest { .. }
+        | ExecuteMsg::RejectBorrowRequest { .. }
+        | ExecuteMsg::CancelBorrowRequest { .. }
+        | ExecuteMsg::PlaceHold { .. }
+        | ExecuteMsg::CancelHold { .. } => Some(PauseCategory::Borrows),
+        ExecuteMsg::ReturnBook { .. } | ExecuteMsg::ClaimDeposit { .. } => {
+            Some(PauseCategory::Returns)
+        }
+        // CW20 payments are checked once `Receive` dispatches its inner message
+        ExecuteMsg::TipAuthor { .. } | ExecuteMsg::PayFees { .. } | ExecuteMsg::SetBorrowPrice { .. } => {
+            Some(PauseCategory::Payments)
+        }
+        _ => None,
+    }
+}
+
+// Name of the role policy entry gating a message, if any.
+// `mint` and `add_book` consult the policy in their handlers, admin messages have their own checks.
+fn policy_action(msg: &ExecuteMsg) -> Option<&'static str> {
+    match msg {
+        ExecuteMsg::TransferNft { .. } => Some("transfer_nft"),
+        ExecuteMsg::SendNft { .. } => Some("send_nft"),
+        ExecuteMsg::Approve { .. } => Some("approve"),
+        ExecuteMsg::Revoke { .. } => Some("revoke"),
+        ExecuteMsg::ApproveAll { .. } => Some("approve_all"),
+        ExecuteMsg::RevokeAll { .. } => Some("revoke_all"),
+        ExecuteMsg::Burn { .. } => Some("burn"),
+        ExecuteMsg::BorrowBook { .. } | ExecuteMsg::BorrowBookFor { .. } => Some("borrow_book"),
+        ExecuteMsg::ReturnBook { .. } => Some("return_book"),
+        ExecuteMsg::RenewLoan { .. } => Some("renew_loan"),
+        ExecuteMsg::PlaceHold { .. } => Some("place_hold"),
+        ExecuteMsg::CancelHold { .. } => Some("cancel_hold"),
+        _ => None,
     }
 }
 
@@ -139,19 +359,32 @@ pub fn execute_send_nft(
     // Validate contract address
     let recipient = deps.api.addr_validate(&contract)?;
 
+    // Only the owner or an approved spender may learn that the book is on loan
+    let token = tokens().load(deps.storage, &token_id)?;
+    assert_can_send(deps.as_ref(), &_env, &info.sender, &token)?;
+
+    // The receiving contract would take the book away from its borrower
+    if loans().has(deps.storage, &token_id) {
+        return Err(ContractError::BookOnLoan {});
+    }
+
     // Transfer the token
     _transfer_nft(deps, &_env, &info, &recipient, &token_id)?;
 
+    // Notify the recipient with the standard `receive_nft` callback
+    let callback = Cw721ReceiveMsg {
+        sender: info.sender.to_string(),
+        token_id: token_id.clone(),
+        msg,
+    }
+    .into_cosmos_msg(recipient.clone())?;
+
     Ok(Response::new()
         .add_attribute("action", "send_nft")
         .add_attribute("sender", info.sender)
-        .add_attribute("recipient", recipient.clone())
+        .add_attribute("recipient", recipient)
         .add_attribute("token_id", token_id)
-        .add_message(WasmMsg::Execute {
-            contract_addr: recipient.to_string(),
-            msg,
-            funds: vec![],
-        }))
+        .add_message(callback))
 }
 
 pub fn execute_approve(
@@ -166,7 +399,7 @@ pub fn execute_approve(
     let spender_addr = deps.api.addr_validate(&spender)?;
 
     // Get token info and verify ownership
-    let mut token = TOKENS.load(deps.storage, &token_id)?;
+    let mut token = tokens().load(deps.storage, &token_id)?;
     
     // Only owner can approve
     if token.owner != info.sender {
@@ -174,7 +407,7 @@ pub fn execute_approve(
     }
 
     // Set the expiration (default: never)
-    let expires = expires.unwrap_or_else(|| Expiration::Never {});
+    let expires = expires.unwrap_or(Expiration::Never {});
     
     // Check if the spender already has approval
     let existing_approval = token.approvals.iter_mut().find(|a| a.spender == spender_addr);
@@ -194,7 +427,7 @@ pub fn execute_approve(
     }
 
     // Save updated token info
-    TOKENS.save(deps.storage, &token_id, &token)?;
+    tokens().save(deps.storage, &token_id, &token)?;
 
     Ok(Response::new()
         .add_attribute("action", "approve")
@@ -214,7 +447,7 @@ pub fn execute_revoke(
     let spender_addr = deps.api.addr_validate(&spender)?;
 
     // Get token info
-    let mut token = TOKENS.load(deps.storage, &token_id)?;
+    let mut token = tokens().load(deps.storage, &token_id)?;
     
     // Only owner can revoke
     if token.owner != info.sender {
@@ -225,7 +458,7 @@ pub fn execute_revoke(
     token.approvals.retain(|a| a.spender != spender_addr);
     
     // Save updated token info
-    TOKENS.save(deps.storage, &token_id, &token)?;
+    tokens().save(deps.storage, &token_id, &token)?;
 
     Ok(Response::new()
         .add_attribute("action", "revoke")
@@ -245,7 +478,7 @@ pub fn execute_approve_all(
     let operator_addr = deps.api.addr_validate(&operator)?;
 
     // Set the expiration (default: never)
-    let expires = expires.unwrap_or_else(|| Expiration::Never {});
+    let expires = expires.unwrap_or(Expiration::Never {});
     
     // Save the operator approval
     OPERATORS.save(deps.storage, (&info.sender, &operator_addr), &expires)?;
@@ -282,28 +515,13 @@ pub fn execute_mint(
     owner: String,
     metadata: Metadata,
 ) -> Result<Response, ContractError> {
+    // Only the minter and the roles allowed by the policy can create new tokens
+    assert_can_mint(deps.as_ref(), &info.sender, "mint")?;
+
     // Validate owner address
     let owner_addr = deps.api.addr_validate(&owner)?;
 
-    // Check if token ID already exists
-    if TOKENS.may_load(deps.storage, &token_id)?.is_some() {
-        return Err(ContractError::Claimed {});
-    }
-
-    // Create token info
-    let token = TokenInfo {
-        owner: owner_addr.clone(),
-        approvals: vec![],
-        metadata,
-    };
-
-    // Save token info
-    TOKENS.save(deps.storage, &token_id, &token)?;
-
-    // Increment token count
-    let mut count = NUM_TOKENS.load(deps.storage)?;
-    count += 1;
-    NUM_TOKENS.save(deps.storage, &count)?;
+    _mint_token(deps, &token_id, owner_addr, metadata)?;
 
     Ok(Response::new()
         .add_attribute("action", "mint")
@@ -319,15 +537,22 @@ pub fn execute_burn(
     token_id: String,
 ) -> Result<Response, ContractError> {
     // Get token info
-    let token = TOKENS.load(deps.storage, &token_id)?;
+    let token = tokens().load(deps.storage, &token_id)?;
     
     // Check if sender is authorized (only owner can burn)
     if token.owner != info.sender {
         return Err(ContractError::Unauthorized {});
     }
 
-    // Remove token
-    TOKENS.remove(deps.storage, &token_id);
+    // Burning would leave the borrower with a dangling loan
+    if loans().has(deps.storage, &token_id) {
+        return Err(ContractError::BookOnLoan {});
+    }
+
+    // Remove token and its waiting list
+    tokens().remove(deps.storage, &token_id)?;
+    HOLDS.remove(deps.storage, &token_id);
+    RESERVATIONS.remove(deps.storage, &token_id);
     
     // Decrement token count
     let mut count = NUM_TOKENS.load(deps.storage)?;
@@ -340,232 +565,1927 @@ pub fn execute_burn(
         .add_attribute("token_id", token_id))
 }
 
-// Helper function to transfer NFT ownership
-fn _transfer_nft(
+pub fn execute_update_minter(
     deps: DepsMut,
-    env: &Env,
-    info: &MessageInfo,
-    recipient: &Addr,
-    token_id: &str,
-) -> Result<(), ContractError> {
-    // Load token info
-    let mut token = TOKENS.load(deps.storage, token_id)?;
+    info: MessageInfo,
+    new_minter: String,
+) -> Result<Response, ContractError> {
+    // Only the current minter can hand over the role
+    assert_minter(deps.as_ref(), &info.sender)?;
 
-    // Check if sender is authorized to transfer
-    if token.owner != info.sender {
-        let mut found = false;
-        
-        // Check token-specific approvals
-        for approval in &token.approvals {
-            if approval.spender == info.sender && !approval.expires.is_expired(&env.block) {
-                found = true;
-                break;
-            }
-        }
-        
-        // Check operator approvals
-        if !found {
-            let op = OPERATORS.may_load(deps.storage, (&token.owner, &info.sender))?;
-            if let Some(expiration) = op {
-                if !expiration.is_expired(&env.block) {
-                    found = true;
-                }
-            }
-        }
-        
-        if !found {
-            return Err(ContractError::Unauthorized {});
-        }
-    }
+    let new_minter = deps.api.addr_validate(&new_minter)?;
+    MINTER.save(deps.storage, &new_minter)?;
 
-    // Update ownership
-    token.owner = recipient.clone();
-    
-    // Remove all existing approvals
-    token.approvals = vec![];
-    
-    // Save updated token info
-    TOKENS.save(deps.storage, token_id, &token)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_minter")
+        .add_attribute("sender", info.sender)
+        .add_attribute("minter", new_minter))
+}
 
-    Ok(())
+pub fn execute_update_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    action: ownership::Action,
+) -> Result<Response, ContractError> {
+    let ownership = ownership::update_ownership(deps, &env.block, &info.sender, action)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_ownership")
+        .add_attribute("sender", info.sender)
+        .add_attributes(ownership.into_attributes()))
 }
 
-/// Adds a new book to the library - legacy support
-fn execute_add_book(
+pub fn execute_grant_role(
     deps: DepsMut,
-    _info: MessageInfo,
-    token_id: String,
-    title: String,
-    author: String,
-    url: String,
-    owner: Addr,
+    info: MessageInfo,
+    role: Role,
+    address: String,
 ) -> Result<Response, ContractError> {
-    // Store in legacy BOOKS map for backwards compatibility
-    let book = Book {
-        title: title.clone(),
-        author: author.clone(),
-        url: url.clone(),
-        owner: owner.clone(),
-    };
-    BOOKS.save(deps.storage, &token_id, &book)?;
-    
-    // Also store as CW721 token
-    let metadata = Metadata {
-        title,
-        author,
-        url,
-    };
-    
-    let token = TokenInfo {
-        owner,
-        approvals: vec![],
-        metadata,
-    };
-    
-    TOKENS.save(deps.storage, &token_id, &token)?;
-    
-    // Increment token count
-    let mut count = NUM_TOKENS.may_load(deps.storage)?.unwrap_or(0);
-    count += 1;
-    NUM_TOKENS.save(deps.storage, &count)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let holder = deps.api.addr_validate(&address)?;
+    ROLES.save(deps.storage, (role.as_str(), &holder), &Empty {})?;
 
     Ok(Response::new()
-        .add_attribute("action", "add_book")
-        .add_attribute("token_id", token_id))
+        .add_attribute("action", "grant_role")
+        .add_attribute("sender", info.sender)
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", holder))
 }
 
-/// Allows a user to borrow a book if it is not already borrowed - legacy support
-fn execute_borrow_book(
+pub fn execute_revoke_role(
     deps: DepsMut,
-    _info: MessageInfo,
-    token_id: String,
-    borrower: Addr,
+    info: MessageInfo,
+    role: Role,
+    address: String,
 ) -> Result<Response, ContractError> {
-    if BORROWERS.may_load(deps.storage, &token_id)?.is_some() {
-        return Err(ContractError::Unauthorized {});
-    }
+    assert_admin(deps.as_ref(), &info.sender)?;
 
-    BORROWERS.save(deps.storage, &token_id, &borrower)?;
+    let holder = deps.api.addr_validate(&address)?;
+    ROLES.remove(deps.storage, (role.as_str(), &holder));
 
     Ok(Response::new()
-        .add_attribute("action", "borrow_book")
-        .add_attribute("token_id", token_id))
+        .add_attribute("action", "revoke_role")
+        .add_attribute("sender", info.sender)
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", holder))
 }
 
-/// Allows the borrower to return a book they have borrowed - legacy support
-fn execute_return_book(
+pub fn execute_set_role_policy(
     deps: DepsMut,
     info: MessageInfo,
-    token_id: String,
+    action: String,
+    roles: Vec<Role>,
 ) -> Result<Response, ContractError> {
-    let borrower = BORROWERS.may_load(deps.storage, &token_id)?;
-    if borrower.is_none() || borrower.unwrap() != info.sender {
-        return Err(ContractError::Unauthorized {});
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    if !POLICY_ACTIONS.contains(&action.as_str()) {
+        return Err(ContractError::UnknownAction { action });
     }
 
-    BORROWERS.remove(deps.storage, &token_id);
+    // An empty list removes the restriction
+    if roles.is_empty() {
+        ROLE_POLICY.remove(deps.storage, &action);
+    } else {
+        ROLE_POLICY.save(deps.storage, &action, &roles)?;
+    }
+
+    let roles_attr = if roles.is_empty() {
+        policy_fallback(&action).to_string()
+    } else {
+        roles.iter().map(|r| r.as_str()).collect::<Vec<_>>().join(",")
+    };
 
     Ok(Response::new()
-        .add_attribute("action", "return_book")
-        .add_attribute("token_id", token_id))
+        .add_attribute("action", "set_role_policy")
+        .add_attribute("sender", info.sender)
+        .add_attribute("policy_action", action)
+        .add_attribute("roles", roles_attr))
 }
 
-// QUERY HANDLERS
+// Who may perform an action without a role policy: the minter creates tokens and borrowers
+// return their own books, every other action is open to everyone
+fn policy_fallback(action: &str) -> &'static str {
+    match action {
+        "mint" | "add_book" => "minter",
+        "force_return" => "borrower",
+        _ => "everyone",
+    }
+}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        // CW721 standard queries
-        QueryMsg::OwnerOf {
-            token_id,
-            include_expired,
-        } => to_json_binary(&query_owner_of(deps, env, token_id, include_expired)?),
-        QueryMsg::Approval {
-            token_id,
-            spender,
-            include_expired,
-        } => to_json_binary(&query_approval(deps, env, token_id, spender, include_expired)?),
-        QueryMsg::Approvals {
-            token_id,
-            include_expired,
-        } => to_json_binary(&query_approvals(deps, env, token_id, include_expired)?),
-        QueryMsg::AllOperators {
-            owner,
-            include_expired,
-            start_after,
-            limit,
-        } => to_json_binary(&query_all_operators(
-            deps,
-            env,
-            owner,
-            include_expired,
-            start_after,
-            limit,
-        )?),
-        QueryMsg::NumTokens {} => to_json_binary(&query_num_tokens(deps)?),
-        QueryMsg::NftInfo { token_id } => to_json_binary(&query_nft_info(deps, token_id)?),
-        QueryMsg::AllNftInfo {
-            token_id,
-            include_expired,
-        } => to_json_binary(&query_all_nft_info(deps, env, token_id, include_expired)?),
-        QueryMsg::Tokens {
-            owner,
-            start_after,
-            limit,
-        } => to_json_binary(&query_tokens(deps, owner, start_after, limit)?),
-        QueryMsg::AllTokens { start_after, limit } => {
-            to_json_binary(&query_all_tokens(deps, start_after, limit)?)
-        }
-        QueryMsg::ContractInfo {} => to_json_binary(&query_contract_info(deps)?),
-        
-        // Legacy queries for backward compatibility
-        QueryMsg::GetBorrower { token_id } => {
-            let borrower = BORROWERS.may_load(deps.storage, &token_id)?;
-            to_json_binary(&borrower)
+pub fn execute_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    categories: Option<Vec<PauseCategory>>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    for category in categories.unwrap_or_else(|| PauseCategory::ALL.to_vec()) {
+        if !paused.contains(&category) {
+            paused.push(category);
         }
-        QueryMsg::GetBook { token_id } => query_book(deps, token_id),
-        QueryMsg::GetAllBooks {} => query_all_books(deps),
-        QueryMsg::GetBorrowedBooks {} => query_borrowed_books(deps),
-        QueryMsg::GetMyBorrowedBooks { borrower } => query_my_borrowed_books(deps, borrower),
-        QueryMsg::GetAvailableBooks {} => query_available_books(deps),
     }
+    PAUSED.save(deps.storage, &paused)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "pause")
+        .add_attribute("sender", info.sender)
+        .add_attribute("paused", categories_attr(&paused)))
 }
 
-fn query_owner_of(
-    deps: Deps,
-    env: Env,
-    token_id: String,
-    include_expired: Option<bool>,
-) -> StdResult<OwnerOfResponse> {
-    let token = TOKENS.load(deps.storage, &token_id)?;
-    
-    let include_expired = include_expired.unwrap_or(false);
-    
-    // Filter out expired approvals
-    let approvals: Vec<_> = token
-        .approvals
-        .iter()
-        .filter(|a| include_expired || !a.expires.is_expired(&env.block))
-        .map(|a| Approval {
-            spender: a.spender.to_string(),
-            expires: a.expires,
-        })
-        .collect();
+pub fn execute_unpause(
+    deps: DepsMut,
+    info: MessageInfo,
+    categories: Option<Vec<PauseCategory>>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
 
-    Ok(OwnerOfResponse {
-        owner: token.owner.to_string(),
-        approvals,
-    })
+    let mut paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    match categories {
+        Some(categories) => paused.retain(|c| !categories.contains(c)),
+        None => paused.clear(),
+    }
+    PAUSED.save(deps.storage, &paused)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unpause")
+        .add_attribute("sender", info.sender)
+        .add_attribute("paused", categories_attr(&paused)))
 }
 
-fn query_approval(
-    deps: Deps,
-    env: Env,
-    token_id: String,
-    spender: String,
-    include_expired: Option<bool>,
-) -> StdResult<ApprovalResponse> {
-    let token = TOKENS.load(deps.storage, &token_id)?;
+// Attribute values can't be empty, nothing paused is written as "none"
+fn categories_attr(categories: &[PauseCategory]) -> String {
+    if categories.is_empty() {
+        return "none".to_string();
+    }
+    categories.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(",")
+}
+
+pub fn execute_update_loan_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    default_loan_period: Option<Duration>,
+    max_renewals: Option<u32>,
+    pickup_window: Option<Duration>,
+    deposit_grace_period: Option<Duration>,
+    transfer_loans: Option<bool>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut config = LOAN_CONFIG.load(deps.storage)?;
+    if let Some(period) = default_loan_period {
+        // Due dates and book loan periods are counted in the unit of the default loan period,
+        // it can't change once set
+        assert_same_unit(&config.default_loan_period, &period)?;
+        config.default_loan_period = period;
+    }
+    if let Some(max_renewals) = max_renewals {
+        config.max_renewals = max_renewals;
+    }
+    if let Some(pickup_window) = pickup_window {
+        config.pickup_window = pickup_window;
+    }
+    if let Some(grace_period) = deposit_grace_period {
+        config.deposit_grace_period = grace_period;
+    }
+    if let Some(transfer_loans) = transfer_loans {
+        config.transfer_loans = transfer_loans;
+    }
+    assert_same_unit(&config.default_loan_period, &config.deposit_grace_period)?;
+    LOAN_CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_loan_config")
+        .add_attribute("sender", info.sender)
+        .add_attribute("default_loan_period", config.default_loan_period.to_string())
+        .add_attribute("max_renewals", config.max_renewals.to_string())
+        .add_attribute("pickup_window", config.pickup_window.to_string())
+        .add_attribute("deposit_grace_period", config.deposit_grace_period.to_string())
+        .add_attribute("transfer_loans", config.transfer_loans.to_string()))
+}
+
+pub fn execute_set_loan_period(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    loan_period: Option<Duration>,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner sets its lending terms
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if let Some(period) = &loan_period {
+        assert_same_unit(&LOAN_CONFIG.load(deps.storage)?.default_loan_period, period)?;
+    }
+
+    token.terms.loan_period = loan_period;
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_loan_period")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute(
+            "loan_period",
+            loan_period.map_or_else(|| "default".to_string(), |p| p.to_string()),
+        ))
+}
+
+// Helper function to check that a category of messages is not paused
+fn assert_not_paused(deps: Deps, category: PauseCategory) -> Result<(), ContractError> {
+    let paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    if paused.contains(&category) {
+        return Err(ContractError::Paused {
+            category: category.as_str().to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Helper function to check that the sender is the contract minter
+fn assert_minter(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let minter = MINTER.load(deps.storage)?;
+    if *sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// Helper function to check that the sender is the minter or holds a role allowed to create tokens
+fn assert_can_mint(deps: Deps, sender: &Addr, action: &str) -> Result<(), ContractError> {
+    let minter = MINTER.load(deps.storage)?;
+    if *sender != minter && !holds_policy_role(deps, sender, action)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// Helper function to check that the sender is the contract owner or an admin
+fn assert_admin(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if !is_owner(deps, sender)? && !has_role(deps, Role::Admin, sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// Helper function to check that the sender satisfies the role policy of an action
+fn assert_role_policy(deps: Deps, sender: &Addr, action: &str) -> Result<(), ContractError> {
+    let roles = ROLE_POLICY.may_load(deps.storage, action)?.unwrap_or_default();
+    if roles.is_empty() || is_owner(deps, sender)? || holds_policy_role(deps, sender, action)? {
+        return Ok(());
+    }
+    Err(ContractError::Unauthorized {})
+}
+
+// Helper function to check whether the sender holds one of the roles the policy lists for an action
+fn holds_policy_role(deps: Deps, sender: &Addr, action: &str) -> StdResult<bool> {
+    let roles = ROLE_POLICY.may_load(deps.storage, action)?.unwrap_or_default();
+    for role in roles {
+        if has_role(deps, role, sender)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn has_role(deps: Deps, role: Role, address: &Addr) -> StdResult<bool> {
+    Ok(ROLES.has(deps.storage, (role.as_str(), address)))
+}
+
+fn is_owner(deps: Deps, sender: &Addr) -> StdResult<bool> {
+    let ownership = ownership::get_ownership(deps.storage)?;
+    Ok(ownership.owner.as_ref() == Some(sender))
+}
+
+// Helper function to store a new token, shared by Mint and AddBook
+fn _mint_token(
+    deps: DepsMut,
+    token_id: &str,
+    owner: Addr,
+    metadata: Metadata,
+) -> Result<(), ContractError> {
+    // Check if token ID already exists
+    if tokens().may_load(deps.storage, token_id)?.is_some() {
+        return Err(ContractError::Claimed {});
+    }
+
+    // Royalties are fixed at mint time
+    if metadata.royalty_percentage.map_or(false, |percentage| percentage > 100) {
+        return Err(ContractError::InvalidRoyaltyPercentage {});
+    }
+    // A royalty needs somewhere to be paid to, and an address is useless without a royalty
+    match (metadata.royalty_percentage, &metadata.royalty_payment_address) {
+        (Some(_), Some(address)) => {
+            deps.api.addr_validate(address)?;
+        }
+        (None, None) => {}
+        _ => return Err(ContractError::IncompleteRoyalty {}),
+    }
+
+    if metadata.isbn.as_deref().map_or(false, |isbn| !valid_isbn(isbn)) {
+        return Err(ContractError::InvalidIsbn {});
+    }
+
+    // Create token info
+    let token = TokenInfo {
+        owner,
+        approvals: vec![],
+        metadata,
+        terms: Default::default(),
+        revenue_split: None,
+    };
+
+    // Save token info
+    tokens().save(deps.storage, token_id, &token)?;
+
+    // Increment token count
+    let mut count = NUM_TOKENS.may_load(deps.storage)?.unwrap_or(0);
+    count += 1;
+    NUM_TOKENS.save(deps.storage, &count)?;
+
+    Ok(())
+}
+
+// Helper function to check the shape of an ISBN-10 or ISBN-13, hyphens and spaces are ignored
+fn valid_isbn(isbn: &str) -> bool {
+    let chars: Vec<char> = isbn.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    match chars.len() {
+        10 => {
+            chars[..9].iter().all(char::is_ascii_digit)
+                && (chars[9].is_ascii_digit() || chars[9] == 'X')
+        }
+        13 => chars.iter().all(char::is_ascii_digit),
+        _ => false,
+    }
+}
+
+// Helper function to check that the sender owns the token or is approved to send it
+fn assert_can_send(deps: Deps, env: &Env, sender: &Addr, token: &TokenInfo) -> Result<(), ContractError> {
+    if token.owner == *sender {
+        return Ok(());
+    }
+
+    // Check token-specific approvals
+    if token
+        .approvals
+        .iter()
+        .any(|approval| approval.spender == *sender && !approval.expires.is_expired(&env.block))
+    {
+        return Ok(());
+    }
+
+    // Check operator approvals
+    let op = OPERATORS.may_load(deps.storage, (&token.owner, sender))?;
+    match op {
+        Some(expiration) if !expiration.is_expired(&env.block) => Ok(()),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+// Helper function to transfer NFT ownership
+fn _transfer_nft(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    recipient: &Addr,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    // Load token info
+    let mut token = tokens().load(deps.storage, token_id)?;
+
+    // Check if sender is authorized to transfer
+    assert_can_send(deps.as_ref(), env, &info.sender, &token)?;
+
+    // Books on loan only change hands if loans are configured to follow the book
+    if loans().has(deps.storage, token_id) && !LOAN_CONFIG.load(deps.storage)?.transfer_loans {
+        return Err(ContractError::BookOnLoan {});
+    }
+
+    // Update ownership
+    token.owner = recipient.clone();
+    
+    // Remove all existing approvals
+    token.approvals = vec![];
+    
+    // Save updated token info
+    tokens().save(deps.storage, token_id, &token)?;
+
+    Ok(())
+}
+
+/// Adds a new book to the library - legacy support
+fn execute_add_book(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    metadata: Metadata,
+    owner: Addr,
+) -> Result<Response, ContractError> {
+    // Books are tokens too, so only the minter and the roles allowed by the policy can add them
+    assert_can_mint(deps.as_ref(), &info.sender, "add_book")?;
+
+    // Books are stored as CW721 tokens carrying the book metadata
+    let owner = deps.api.addr_validate(owner.as_str())?;
+    _mint_token(deps, &token_id, owner, metadata)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_book")
+        .add_attribute("token_id", token_id))
+}
+
+/// Allows a user to borrow a book if it is not already borrowed - legacy support
+fn execute_borrow_book(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let borrower = info.sender.clone();
+    _borrow_book(deps, env, &info, token_id, borrower)
+}
+
+/// Allows an approved delegate to borrow a book on behalf of a borrower
+fn execute_borrow_book_for(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    borrower: String,
+) -> Result<Response, ContractError> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+
+    // The borrower must have approved the sender as a delegate
+    let expires = BORROW_DELEGATES
+        .may_load(deps.storage, (&borrower, &info.sender))?
+        .ok_or(ContractError::Unauthorized {})?;
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // The borrower, not only the delegate, has to satisfy the borrowing policy
+    assert_role_policy(deps.as_ref(), &borrower, "borrow_book")?;
+
+    _borrow_book(deps, env, &info, token_id, borrower)
+}
+
+pub fn execute_approve_borrow_delegate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    delegate: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let delegate_addr = deps.api.addr_validate(&delegate)?;
+
+    // Set the expiration (default: never)
+    let expires = expires.unwrap_or(Expiration::Never {});
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    BORROW_DELEGATES.save(deps.storage, (&info.sender, &delegate_addr), &expires)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "approve_borrow_delegate")
+        .add_attribute("sender", info.sender)
+        .add_attribute("delegate", delegate))
+}
+
+pub fn execute_revoke_borrow_delegate(
+    deps: DepsMut,
+    info: MessageInfo,
+    delegate: String,
+) -> Result<Response, ContractError> {
+    let delegate_addr = deps.api.addr_validate(&delegate)?;
+
+    BORROW_DELEGATES.remove(deps.storage, (&info.sender, &delegate_addr));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_borrow_delegate")
+        .add_attribute("sender", info.sender)
+        .add_attribute("delegate", delegate))
+}
+
+// Helper function to borrow a book according to its lending policy
+fn _borrow_book(
+    mut deps: DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    token_id: String,
+    borrower: Addr,
+) -> Result<Response, ContractError> {
+    let token = tokens()
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::BookNotFound {})?;
+    assert_no_outstanding_fees(deps.storage, &borrower)?;
+
+    // Owners take their own books for free, everyone else pays the price and locks the deposit if set
+    let (payment, deposit) = if token.owner != borrower {
+        (token.terms.price.clone(), token.terms.deposit.clone())
+    } else {
+        (None, None)
+    };
+    let required: Vec<Coin> = payment.iter().chain(deposit.iter()).cloned().collect();
+    assert_exact_funds(info, &required)?;
+
+    // Owners can always take their own books
+    if token.owner != borrower {
+        match token.terms.policy {
+            LendingPolicy::Open => {}
+            LendingPolicy::MembersOnly => {
+                if !has_role(deps.as_ref(), Role::Member, &borrower)? {
+                    return Err(ContractError::MembersOnly {});
+                }
+            }
+            LendingPolicy::ApprovalRequired => {
+                let request = BorrowRequest {
+                    token_id,
+                    requester: borrower,
+                    owner: token.owner,
+                    created_at: env.block.time,
+                    payment,
+                    deposit,
+                };
+                return _request_borrow(deps, info, request);
+            }
+            LendingPolicy::Closed => return Err(ContractError::LendingClosed {}),
+        }
+    }
+
+    if let Some(deposit) = &deposit {
+        add_to_total(deps.storage, &ESCROWED_DEPOSITS, deposit)?;
+    }
+    let loan = _lend(deps.branch(), &env, &token_id, &token, borrower, deposit)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "borrow_book")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id)
+        .add_attribute("borrower", loan.borrower)
+        .add_attribute("due", loan.due.to_string());
+    if let Some(deposit) = loan.deposit {
+        response = response.add_attribute("deposit", deposit.to_string());
+    }
+    if let Some(payment) = payment {
+        response = _pay_revenue(deps.storage, response, &token, payment, "borrow_fee")?;
+    }
+    Ok(response)
+}
+
+// Helper function to check that exactly the required coins were sent
+fn assert_exact_funds(info: &MessageInfo, required: &[Coin]) -> Result<(), ContractError> {
+    if required.is_empty() {
+        nonpayable(info)?;
+        return Ok(());
+    }
+
+    // Price and deposit may share a denom
+    let mut expected: Vec<Coin> = vec![];
+    for coin in required {
+        match expected.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(c) => c.amount += coin.amount,
+            None => expected.push(coin.clone()),
+        }
+    }
+
+    for sent in &info.funds {
+        if !expected.iter().any(|c| c.denom == sent.denom) {
+            return Err(ContractError::WrongDenom {
+                expected: coins_to_string(&expected),
+                sent: sent.denom.clone(),
+            });
+        }
+    }
+
+    for coin in &expected {
+        let sent = info
+            .funds
+            .iter()
+            .filter(|c| c.denom == coin.denom)
+            .map(|c| c.amount)
+            .sum::<Uint128>();
+        let sent = Coin {
+            denom: coin.denom.clone(),
+            amount: sent,
+        };
+        if sent.amount < coin.amount {
+            return Err(ContractError::InsufficientFunds {
+                required: coin.to_string(),
+                sent: sent.to_string(),
+            });
+        }
+        if sent.amount > coin.amount {
+            return Err(ContractError::ExcessFunds {
+                required: coin.to_string(),
+                sent: sent.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn coins_to_string(coins: &[Coin]) -> String {
+    coins.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(",")
+}
+
+// Helper function to share a borrow fee or tip between the book owner, its author and the
+// platform according to the book's revenue split, keeping the platform fee in the treasury
+fn _pay_revenue(
+    storage: &mut dyn Storage,
+    response: Response,
+    token: &TokenInfo,
+    payment: Coin,
+    kind: &str,
+) -> StdResult<Response> {
+    let shares = revenue_shares(storage, token)?;
+    let platform_fee = payment.amount.multiply_ratio(shares.platform_bps, 10_000u128);
+    let author_share = payment.amount.multiply_ratio(shares.author_bps, 10_000u128);
+    // The owner gets whatever rounding leaves
+    let owner_amount = payment.amount - platform_fee - author_share;
+    if !platform_fee.is_zero() {
+        TREASURY.update(storage, &payment.denom, |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default() + platform_fee)
+        })?;
+    }
+
+    let mut response = response
+        .add_attribute(kind, payment.to_string())
+        .add_attribute("platform_fee", Coin::new(platform_fee.u128(), &payment.denom).to_string())
+        .add_messages(payout_msgs(
+            &token.owner,
+            vec![Coin::new(owner_amount.u128(), &payment.denom)],
+        )?);
+    if let Some(author) = &shares.author {
+        response = response
+            .add_attribute("author_share", Coin::new(author_share.u128(), &payment.denom).to_string())
+            .add_messages(payout_msgs(
+                author,
+                vec![Coin::new(author_share.u128(), &payment.denom)],
+            )?);
+    }
+    Ok(response)
+}
+
+// Shares of a book's revenue in basis points
+struct RevenueShares {
+    author: Option<Addr>,
+    owner_bps: u16,
+    author_bps: u16,
+    platform_bps: u16,
+}
+
+// Helper function to find how a book's revenue is shared. The platform always gets at least the
+// configured fee: if it was raised after the split was set, the difference comes out of the
+// owner's share first, then the author's.
+fn revenue_shares(storage: &dyn Storage, token: &TokenInfo) -> StdResult<RevenueShares> {
+    let platform_fee_bps = FEE_CONFIG.may_load(storage)?.unwrap_or_default().platform_fee_bps;
+    let shares = match &token.revenue_split {
+        Some(split) => {
+            let platform_bps = split.platform_bps.max(platform_fee_bps);
+            let shortfall = platform_bps - split.platform_bps;
+            let author_bps = split.author_bps - shortfall.saturating_sub(split.owner_bps);
+            RevenueShares {
+                author: Some(split.author.clone()),
+                owner_bps: 10_000 - platform_bps - author_bps,
+                author_bps,
+                platform_bps,
+            }
+        }
+        None => RevenueShares {
+            author: None,
+            owner_bps: 10_000 - platform_fee_bps,
+            author_bps: 0,
+            platform_bps: platform_fee_bps,
+        },
+    };
+    Ok(shares)
+}
+
+// Helper function to record a request to borrow an approval-required book
+fn _request_borrow(
+    deps: DepsMut,
+    info: &MessageInfo,
+    request: BorrowRequest,
+) -> Result<Response, ContractError> {
+    // One pending request per book and requester
+    let pending = borrow_requests()
+        .idx
+        .requester
+        .prefix(request.requester.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .any(|item| matches!(item, Ok((_, pending)) if pending.token_id == request.token_id));
+    if pending {
+        return Err(ContractError::AlreadyRequested {});
+    }
+
+    let request_id = BORROW_REQUEST_SEQ.may_load(deps.storage)?.unwrap_or(0) + 1;
+    BORROW_REQUEST_SEQ.save(deps.storage, &request_id)?;
+
+    borrow_requests().save(deps.storage, request_id, &request)?;
+    if let Some(payment) = &request.payment {
+        add_to_total(deps.storage, &PENDING_PAYMENTS, payment)?;
+    }
+    if let Some(deposit) = &request.deposit {
+        add_to_total(deps.storage, &ESCROWED_DEPOSITS, deposit)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "request_borrow")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", request.token_id)
+        .add_attribute("requester", request.requester)
+        .add_attribute("request_id", request_id.to_string()))
+}
+
+// Helper function to check out a book to a borrower
+fn _lend(
+    deps: DepsMut,
+    env: &Env,
+    token_id: &str,
+    token: &TokenInfo,
+    borrower: Addr,
+    deposit: Option<Coin>,
+) -> Result<Loan, ContractError> {
+    if loans().has(deps.storage, token_id) {
+        return Err(ContractError::AlreadyBorrowed {});
+    }
+
+    // A reserved book can only be picked up by its holder
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let (reservation, holds) = load_reservation(deps.as_ref(), env, &config, token_id)?;
+    if let Some(reservation) = &reservation {
+        if reservation.holder != borrower {
+            return Err(ContractError::BookReserved {});
+        }
+    }
+    save_reservation(deps.storage, token_id, None, &holds)?;
+
+    // The book's own loan period takes precedence over the default one
+    let loan_period = token.terms.loan_period.unwrap_or(config.default_loan_period);
+
+    let loan = Loan {
+        borrower,
+        start_time: env.block.time,
+        start_height: env.block.height,
+        due: loan_period.after(&env.block),
+        renewals: 0,
+        deposit,
+        late_fee: effective_late_fee(deps.storage, token_id)?,
+    };
+    loans().save(deps.storage, token_id, &loan)?;
+
+    Ok(loan)
+}
+
+pub fn execute_set_lending_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    policy: LendingPolicy,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner sets its lending terms
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    token.terms.policy = policy;
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_lending_policy")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("policy", policy.as_str()))
+}
+
+/// Allows the book owner to lend the book of a pending request to its requester
+fn execute_accept_borrow_request(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    request_id: u64,
+) -> Result<Response, ContractError> {
+    let request = borrow_requests()
+        .may_load(deps.storage, request_id)?
+        .ok_or(ContractError::RequestNotFound {})?;
+
+    // The book may have changed hands since the request was made
+    let token = tokens()
+        .may_load(deps.storage, &request.token_id)?
+        .ok_or(ContractError::BookNotFound {})?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    // The requester may have returned another book late since the request was made
+    assert_no_outstanding_fees(deps.storage, &request.requester)?;
+
+    borrow_requests().remove(deps.storage, request_id)?;
+    // The deposit held with the request stays in escrow for the loan
+    let loan = _lend(
+        deps.branch(),
+        &env,
+        &request.token_id,
+        &token,
+        request.requester,
+        request.deposit,
+    )?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "accept_borrow_request")
+        .add_attribute("sender", info.sender)
+        .add_attribute("request_id", request_id.to_string())
+        .add_attribute("token_id", request.token_id)
+        .add_attribute("borrower", loan.borrower)
+        .add_attribute("due", loan.due.to_string());
+    if let Some(payment) = request.payment {
+        sub_from_total(deps.storage, &PENDING_PAYMENTS, &payment)?;
+        response = _pay_revenue(deps.storage, response, &token, payment, "borrow_fee")?;
+    }
+    Ok(response)
+}
+
+/// Allows the book owner to turn down a pending borrow request
+fn execute_reject_borrow_request(
+    deps: DepsMut,
+    info: MessageInfo,
+    request_id: u64,
+) -> Result<Response, ContractError> {
+    let request = borrow_requests()
+        .may_load(deps.storage, request_id)?
+        .ok_or(ContractError::RequestNotFound {})?;
+
+    // Requests for burned books can be cleared by the owner they were addressed to
+    let owner = tokens()
+        .may_load(deps.storage, &request.token_id)?
+        .map_or(request.owner.clone(), |token| token.owner);
+    if owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    borrow_requests().remove(deps.storage, request_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "reject_borrow_request")
+        .add_attribute("sender", info.sender)
+        .add_attribute("request_id", request_id.to_string())
+        .add_attribute("token_id", request.token_id.clone())
+        .add_attribute("requester", request.requester.clone())
+        .add_messages(_refund_request(deps.storage, &request)?))
+}
+
+/// Allows the requester to withdraw a pending borrow request
+fn execute_cancel_borrow_request(
+    deps: DepsMut,
+    info: MessageInfo,
+    request_id: u64,
+) -> Result<Response, ContractError> {
+    let request = borrow_requests()
+        .may_load(deps.storage, request_id)?
+        .ok_or(ContractError::RequestNotFound {})?;
+
+    if request.requester != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    borrow_requests().remove(deps.storage, request_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_borrow_request")
+        .add_attribute("sender", info.sender)
+        .add_attribute("request_id", request_id.to_string())
+        .add_attribute("token_id", request.token_id.clone())
+        .add_messages(_refund_request(deps.storage, &request)?))
+}
+
+// Helper function to give back the borrow fee and deposit held for a request
+fn _refund_request(storage: &mut dyn Storage, request: &BorrowRequest) -> StdResult<Vec<CosmosMsg>> {
+    if let Some(payment) = &request.payment {
+        sub_from_total(storage, &PENDING_PAYMENTS, payment)?;
+    }
+    if let Some(deposit) = &request.deposit {
+        sub_from_total(storage, &ESCROWED_DEPOSITS, deposit)?;
+    }
+    let refund: Vec<Coin> = request
+        .payment
+        .iter()
+        .chain(request.deposit.iter())
+        .cloned()
+        .collect();
+    payout_msgs(&request.requester, refund)
+}
+
+// Helper function to record funds entering a per-denom total, such as the deposits in escrow
+fn add_to_total(storage: &mut dyn Storage, totals: &Map<&str, Uint128>, coin: &Coin) -> StdResult<()> {
+    totals.update(storage, &coin.denom, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() + coin.amount)
+    })?;
+    Ok(())
+}
+
+// Helper function to record funds leaving a per-denom total
+fn sub_from_total(storage: &mut dyn Storage, totals: &Map<&str, Uint128>, coin: &Coin) -> StdResult<()> {
+    let total = totals.may_load(storage, &coin.denom)?.unwrap_or_default();
+    let remaining = total.checked_sub(coin.amount)?;
+    if remaining.is_zero() {
+        totals.remove(storage, &coin.denom);
+    } else {
+        totals.save(storage, &coin.denom, &remaining)?;
+    }
+    Ok(())
+}
+
+pub fn execute_set_deposit(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    deposit: Option<Coin>,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner sets its lending terms
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // A zero deposit is the same as no deposit
+    let deposit = deposit.filter(|d| !d.amount.is_zero());
+    if let Some(deposit) = &deposit {
+        assert_accepted_denom(deps.storage, &deposit.denom)?;
+    }
+    token.terms.deposit = deposit.clone();
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_deposit")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("deposit", deposit.map_or_else(|| "none".to_string(), |d| d.to_string())))
+}
+
+/// Allows the book owner to take the deposit of a loan overdue past the grace period, ending the loan
+fn execute_claim_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let token = tokens()
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::BookNotFound {})?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let loan = loans()
+        .may_load(deps.storage, &token_id)?
+        .ok_or_else(|| ContractError::LoanNotFound {
+            token_id: token_id.clone(),
+        })?;
+    let deposit = loan.deposit.clone().ok_or(ContractError::NoDeposit {})?;
+
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let claimable_from = (loan.due + config.deposit_grace_period)?;
+    if !claimable_from.is_expired(&env.block) {
+        return Err(ContractError::DepositNotClaimable {});
+    }
+
+    sub_from_total(deps.storage, &ESCROWED_DEPOSITS, &deposit)?;
+    let reservation = _close_loan(deps, &env, &token_id)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "claim_deposit")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("borrower", loan.borrower)
+        .add_attribute("token_id", token_id)
+        .add_attribute("deposit", deposit.to_string())
+        .add_messages(payout_msgs(&info.sender, vec![deposit])?);
+    if let Some(reservation) = reservation {
+        response = response
+            .add_attribute("reserved_for", reservation.holder)
+            .add_attribute("reserved_until", reservation.expires.to_string());
+    }
+    Ok(response)
+}
+
+pub fn execute_set_borrow_price(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    price: Option<Coin>,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner sets its lending terms
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // A zero price is the same as no price
+    let price = price.filter(|p| !p.amount.is_zero());
+    if let Some(price) = &price {
+        assert_accepted_denom(deps.storage, &price.denom)?;
+    }
+    token.terms.price = price.clone();
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_borrow_price")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("price", price.map_or_else(|| "free".to_string(), |p| p.to_string())))
+}
+
+pub fn execute_update_fee_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    platform_fee_bps: u16,
+    late_fee: Option<LateFee>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    if platform_fee_bps > 10_000 {
+        return Err(ContractError::InvalidFee {});
+    }
+    validate_late_fee(deps.storage, late_fee.as_ref())?;
+    let late_fee_attr = late_fee_attribute(late_fee.as_ref());
+    FEE_CONFIG.save(
+        deps.storage,
+        &FeeConfig {
+            platform_fee_bps,
+            late_fee,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_fee_config")
+        .add_attribute("sender", info.sender)
+        .add_attribute("platform_fee_bps", platform_fee_bps.to_string())
+        .add_attribute("late_fee", late_fee_attr))
+}
+
+pub fn execute_set_late_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    late_fee: Option<LateFee>,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner sets its lending terms
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    validate_late_fee(deps.storage, late_fee.as_ref())?;
+    let late_fee_attr = late_fee_attribute(late_fee.as_ref());
+    token.terms.late_fee = late_fee;
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_late_fee")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("late_fee", late_fee_attr))
+}
+
+// Helper function to reject durations added to due dates counted in another unit,
+// adding a height to a time (or the reverse) fails
+fn assert_same_unit(expected: &Duration, duration: &Duration) -> Result<(), ContractError> {
+    match (expected, duration) {
+        (Duration::Height(_), Duration::Height(_)) | (Duration::Time(_), Duration::Time(_)) => Ok(()),
+        _ => Err(ContractError::MismatchedDurations {}),
+    }
+}
+
+// Helper function to reject late fees charged per an empty period or per a unit loans aren't due in
+fn validate_late_fee(storage: &dyn Storage, late_fee: Option<&LateFee>) -> Result<(), ContractError> {
+    if let Some(late_fee) = late_fee {
+        assert_accepted_denom(storage, &late_fee.amount.denom)?;
+        let config = LOAN_CONFIG.load(storage)?;
+        assert_same_unit(&config.default_loan_period, &late_fee.per)?;
+    }
+    if matches!(
+        late_fee.map(|fee| fee.per),
+        Some(Duration::Height(0)) | Some(Duration::Time(0))
+    ) {
+        return Err(ContractError::InvalidLateFee {});
+    }
+    Ok(())
+}
+
+fn late_fee_attribute(late_fee: Option<&LateFee>) -> String {
+    late_fee.map_or_else(
+        || "none".to_string(),
+        |fee| format!("{} per {}", fee.amount, fee.per),
+    )
+}
+
+// Helper function to check that a CW20 denom belongs to an accepted contract, native denoms always pass
+fn assert_accepted_denom(storage: &dyn Storage, denom: &str) -> Result<(), ContractError> {
+    if let Some(contract) = cw20_contract(denom) {
+        if !ACCEPTED_CW20.has(storage, &Addr::unchecked(contract)) {
+            return Err(ContractError::Cw20NotAccepted {
+                address: contract.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Unwraps a payment made with an accepted CW20 token and handles the inner message
+/// as if it was sent by the token holder with the tokens attached as funds
+fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    // The caller is the token contract, it must be accepted as payment
+    if !ACCEPTED_CW20.has(deps.storage, &info.sender) {
+        return Err(ContractError::Cw20NotAccepted {
+            address: info.sender.to_string(),
+        });
+    }
+
+    let msg = match from_json(&wrapper.msg)? {
+        ReceiveMsg::BorrowBook { token_id } => ExecuteMsg::BorrowBook { token_id },
+        ReceiveMsg::PayFees {} => ExecuteMsg::PayFees {},
+        ReceiveMsg::TipAuthor { token_id } => ExecuteMsg::TipAuthor { token_id },
+    };
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Coin {
+            denom: cw20_denom(&info.sender),
+            amount: wrapper.amount,
+        }],
+    };
+
+    // Go through the usual pause and role policy checks for the inner message
+    execute(deps, env, info, msg)
+}
+
+/// Sends the attached funds to the author of a book
+fn execute_tip_author(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let token = tokens()
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::BookNotFound {})?;
+    if info.funds.iter().all(|coin| coin.amount.is_zero()) {
+        return Err(PaymentError::NoFunds {}.into());
+    }
+
+    // Without a revenue split tips go to the owner, who is the author for self-published books
+    let mut response = Response::new()
+        .add_attribute("action", "tip_author")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id);
+    for tip in info.funds.into_iter().filter(|coin| !coin.amount.is_zero()) {
+        response = _pay_revenue(deps.storage, response, &token, tip, "tip")?;
+    }
+    Ok(response)
+}
+
+pub fn execute_set_revenue_split(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    split: Option<RevenueSplitMsg>,
+) -> Result<Response, ContractError> {
+    let mut token = tokens().load(deps.storage, &token_id)?;
+
+    // Only the book owner decides how its revenue is shared
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    token.revenue_split = match split {
+        Some(split) => {
+            let total = split.owner_bps as u32 + split.author_bps as u32 + split.platform_bps as u32;
+            if total != 10_000 {
+                return Err(ContractError::InvalidSplit {});
+            }
+            // The platform fee set by the admins can't be waived by book owners
+            let min = FEE_CONFIG.may_load(deps.storage)?.unwrap_or_default().platform_fee_bps;
+            if split.platform_bps < min {
+                return Err(ContractError::PlatformShareTooLow { min });
+            }
+            Some(RevenueSplit {
+                author: deps.api.addr_validate(&split.author)?,
+                owner_bps: split.owner_bps,
+                author_bps: split.author_bps,
+                platform_bps: split.platform_bps,
+            })
+        }
+        None => None,
+    };
+    tokens().save(deps.storage, &token_id, &token)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "set_revenue_split")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id);
+    if let Some(split) = token.revenue_split {
+        response = response
+            .add_attribute("author", split.author)
+            .add_attribute("owner_bps", split.owner_bps.to_string())
+            .add_attribute("author_bps", split.author_bps.to_string())
+            .add_attribute("platform_bps", split.platform_bps.to_string());
+    }
+    Ok(response)
+}
+
+pub fn execute_withdraw_treasury(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let available = TREASURY.may_load(deps.storage, &amount.denom)?.unwrap_or_default();
+    if amount.amount.is_zero() || amount.amount > available {
+        return Err(ContractError::InsufficientTreasury {
+            available: Coin::new(available.u128(), &amount.denom).to_string(),
+        });
+    }
+
+    // The contract must actually hold the treasury on top of what it owes borrowers
+    let balance = query_balance(&deps.querier, &env.contract.address, &amount.denom)?;
+    let required = available + held_for_borrowers(deps.storage, &amount.denom)?;
+    if balance < required {
+        return Err(ContractError::TreasuryImbalance {
+            balance: Coin::new(balance.u128(), &amount.denom).to_string(),
+            required: Coin::new(required.u128(), &amount.denom).to_string(),
+        });
+    }
+
+    let remaining = available - amount.amount;
+    if remaining.is_zero() {
+        TREASURY.remove(deps.storage, &amount.denom);
+    } else {
+        TREASURY.save(deps.storage, &amount.denom, &remaining)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_treasury")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", recipient.clone())
+        .add_attribute("amount", amount.to_string())
+        .add_messages(payout_msgs(&recipient, vec![amount])?))
+}
+
+pub fn execute_update_accepted_cw20(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    for address in &add {
+        let address = deps.api.addr_validate(address)?;
+        ACCEPTED_CW20.save(deps.storage, &address, &Empty {})?;
+    }
+    for address in &remove {
+        let address = deps.api.addr_validate(address)?;
+        ACCEPTED_CW20.remove(deps.storage, &address);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_accepted_cw20")
+        .add_attribute("sender", info.sender)
+        .add_attribute("added", addresses_attr(&add))
+        .add_attribute("removed", addresses_attr(&remove)))
+}
+
+// Attribute values can't be empty, an empty list is written as "none"
+fn addresses_attr(addresses: &[String]) -> String {
+    if addresses.is_empty() {
+        return "none".to_string();
+    }
+    addresses.join(",")
+}
+
+// Helper function to find the late fee that applies to a book
+fn effective_late_fee(storage: &dyn Storage, token_id: &str) -> StdResult<Option<LateFee>> {
+    let terms_fee = tokens()
+        .may_load(storage, token_id)?
+        .and_then(|token| token.terms.late_fee);
+    match terms_fee {
+        Some(fee) => Ok(Some(fee)),
+        None => Ok(FEE_CONFIG.may_load(storage)?.and_then(|config| config.late_fee)),
+    }
+}
+
+// Helper function to compute the late fee accrued by a loan at the given block,
+// every started period past the due date is charged in full. The fee saturates instead of overflowing
+fn accrued_late_fee(late_fee: &LateFee, due: &Expiration, block: &BlockInfo) -> Option<Coin> {
+    let (overdue, per) = match (due, late_fee.per) {
+        (Expiration::AtTime(due), Duration::Time(per)) if block.time > *due => {
+            (block.time.seconds() - due.seconds(), per)
+        }
+        (Expiration::AtHeight(due), Duration::Height(per)) if block.height > *due => {
+            (block.height - due, per)
+        }
+        _ => return None,
+    };
+    // Overdue is at least 1 here, this can't overflow for any period
+    let periods = (overdue - 1) / per + 1;
+    let amount = late_fee.amount.amount.saturating_mul(Uint128::from(periods));
+    if amount.is_zero() {
+        return None;
+    }
+    Some(Coin {
+        denom: late_fee.amount.denom.clone(),
+        amount,
+    })
+}
+
+// Helper function to refuse new loans to borrowers with unpaid late fees
+fn assert_no_outstanding_fees(storage: &dyn Storage, borrower: &Addr) -> Result<(), ContractError> {
+    let unpaid = OUTSTANDING_FEES
+        .prefix(borrower)
+        .range(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    if unpaid {
+        return Err(ContractError::OutstandingFees {});
+    }
+    Ok(())
+}
+
+/// Pays late fees left unpaid at return with the attached funds, oldest books first
+fn execute_pay_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    if info.funds.is_empty() {
+        return Err(PaymentError::NoFunds {}.into());
+    }
+
+    let mut fees = OUTSTANDING_FEES
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    if fees.is_empty() {
+        return Err(ContractError::NoOutstandingFees {});
+    }
+    fees.sort_by_key(|(_, fee)| fee.incurred_at);
+
+    let mut response = Response::new()
+        .add_attribute("action", "pay_fees")
+        .add_attribute("sender", info.sender.clone());
+
+    for sent in &info.funds {
+        // Only the denoms and amounts owed are accepted
+        if !fees.iter().any(|(_, fee)| fee.amount.denom == sent.denom) {
+            return Err(ContractError::WrongDenom {
+                expected: fees
+                    .iter()
+                    .map(|(_, fee)| fee.amount.denom.clone())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                sent: sent.denom.clone(),
+            });
+        }
+
+        let mut remaining = sent.amount;
+        for (token_id, fee) in fees.iter_mut() {
+            if fee.amount.denom != sent.denom || fee.amount.amount.is_zero() || remaining.is_zero() {
+                continue;
+            }
+            let paid = remaining.min(fee.amount.amount);
+            remaining -= paid;
+            fee.amount.amount -= paid;
+            if fee.amount.amount.is_zero() {
+                OUTSTANDING_FEES.remove(deps.storage, (&info.sender, token_id));
+            } else {
+                OUTSTANDING_FEES.save(deps.storage, (&info.sender, token_id), fee)?;
+            }
+            response = response
+                .add_attribute("paid", format!("{}:{}{}", token_id, paid, sent.denom))
+                .add_messages(payout_msgs(&fee.owner, vec![Coin::new(paid.u128(), &sent.denom)])?);
+        }
+
+        if !remaining.is_zero() {
+            return Err(ContractError::ExcessFunds {
+                required: Coin::new((sent.amount - remaining).u128(), &sent.denom).to_string(),
+                sent: sent.to_string(),
+            });
+        }
+    }
+
+    Ok(response)
+}
+
+/// Allows the borrower to return a book they have borrowed - legacy support
+fn execute_return_book(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let loan = loans()
+        .may_load(deps.storage, &token_id)?
+        .ok_or_else(|| ContractError::LoanNotFound {
+            token_id: token_id.clone(),
+        })?;
+    let borrower = loan.borrower;
+
+    // Staff allowed by the policy can return a book on behalf of its borrower
+    if borrower != info.sender && !holds_policy_role(deps.as_ref(), &info.sender, "force_return")? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let late_fee = loan
+        .late_fee
+        .as_ref()
+        .and_then(|late_fee| accrued_late_fee(late_fee, &loan.due, &env.block));
+    let reservation = _close_loan(deps.branch(), &env, &token_id)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "return_book")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("borrower", borrower.clone())
+        .add_attribute("token_id", token_id.clone());
+
+    let mut deposit = loan.deposit;
+    if let Some(deposit) = &deposit {
+        sub_from_total(deps.storage, &ESCROWED_DEPOSITS, deposit)?;
+    }
+
+    match late_fee {
+        Some(late_fee) => {
+            // The late fee is taken from the deposit first, then from the attached funds
+            let mut owed = late_fee.amount;
+            let mut paid = Uint128::zero();
+            if let Some(deposit) = deposit.as_mut() {
+                if deposit.denom == late_fee.denom {
+                    let taken = deposit.amount.min(owed);
+                    deposit.amount -= taken;
+                    owed -= taken;
+                    paid += taken;
+                }
+            }
+            let sent = fee_funds(&info, &late_fee.denom, owed)?;
+            owed -= sent;
+            paid += sent;
+
+            let owner = tokens().load(deps.storage, &token_id)?.owner;
+            response = response.add_attribute("late_fee", late_fee.to_string());
+            response = response.add_messages(payout_msgs(
+                &owner,
+                vec![Coin::new(paid.u128(), &late_fee.denom)],
+            )?);
+            // Whatever is left is owed before the next loan
+            if !owed.is_zero() {
+                let unpaid = Coin::new(owed.u128(), &late_fee.denom);
+                response = response.add_attribute("late_fee_unpaid", unpaid.to_string());
+                OUTSTANDING_FEES.save(
+                    deps.storage,
+                    (&borrower, &token_id),
+                    &OutstandingFee {
+                        owner,
+                        amount: unpaid,
+                        incurred_at: env.block.time,
+                    },
+                )?;
+            }
+        }
+        None => {
+            nonpayable(&info)?;
+        }
+    }
+
+    // The rest of the deposit goes back to the borrower
+    if let Some(deposit) = deposit.filter(|deposit| !deposit.amount.is_zero()) {
+        response = response
+            .add_attribute("deposit_refund", deposit.to_string())
+            .add_messages(payout_msgs(&borrower, vec![deposit])?);
+    }
+    if let Some(reservation) = reservation {
+        response = response
+            .add_attribute("reserved_for", reservation.holder)
+            .add_attribute("reserved_until", reservation.expires.to_string());
+    }
+    Ok(response)
+}
+
+// Helper function to read the funds paying a late fee, at most the amount owed in its denom
+fn fee_funds(info: &MessageInfo, denom: &str, owed: Uint128) -> Result<Uint128, ContractError> {
+    let mut sent = Uint128::zero();
+    for coin in &info.funds {
+        if coin.denom != denom {
+            return Err(ContractError::WrongDenom {
+                expected: denom.to_string(),
+                sent: coin.denom.clone(),
+            });
+        }
+        sent += coin.amount;
+    }
+    if sent > owed {
+        return Err(ContractError::ExcessFunds {
+            required: Coin::new(owed.u128(), denom).to_string(),
+            sent: Coin::new(sent.u128(), denom).to_string(),
+        });
+    }
+    Ok(sent)
+}
+
+// Helper function to end the loan of a book and set it aside for the first holder in line
+fn _close_loan(deps: DepsMut, env: &Env, token_id: &str) -> StdResult<Option<Reservation>> {
+    loans().remove(deps.storage, token_id)?;
+
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let (mut reservation, mut holds) = load_reservation(deps.as_ref(), env, &config, token_id)?;
+    if reservation.is_none() && !holds.is_empty() {
+        let next = holds.remove(0);
+        reservation = Some(Reservation {
+            holder: next.holder,
+            expires: config.pickup_window.after(&env.block),
+        });
+    }
+    save_reservation(deps.storage, token_id, reservation.as_ref(), &holds)?;
+
+    Ok(reservation)
+}
+
+/// Allows the borrower to extend the due date of a loan by the loan period
+fn execute_renew_loan(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let mut loan = loans()
+        .may_load(deps.storage, &token_id)?
+        .ok_or_else(|| ContractError::LoanNotFound {
+            token_id: token_id.clone(),
+        })?;
+
+    if loan.borrower != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    if loan.renewals >= config.max_renewals {
+        return Err(ContractError::MaxRenewalsReached {
+            max: config.max_renewals,
+        });
+    }
+
+    // Renewing would wipe out the late fee accrued so far
+    if let Some(late_fee) = &loan.late_fee {
+        if accrued_late_fee(late_fee, &loan.due, &env.block).is_some() {
+            return Err(ContractError::LoanOverdue {});
+        }
+    }
+
+    // Members waiting for the book take precedence over a renewal
+    if !HOLDS.may_load(deps.storage, &token_id)?.unwrap_or_default().is_empty() {
+        return Err(ContractError::BookReserved {});
+    }
+
+    // Renewals use the same period as the loan itself
+    let token = tokens().load(deps.storage, &token_id)?;
+    let loan_period = token.terms.loan_period.unwrap_or(config.default_loan_period);
+
+    loan.due = (loan.due + loan_period)?;
+    loan.renewals += 1;
+    loans().save(deps.storage, &token_id, &loan)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "renew_loan")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("due", loan.due.to_string())
+        .add_attribute("renewals", loan.renewals.to_string()))
+}
+
+/// Adds the sender to the queue of members waiting for a borrowed or reserved book
+fn execute_place_hold(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    if !tokens().has(deps.storage, &token_id) {
+        return Err(ContractError::TokenNotFound {});
+    }
+
+    let loan = loans().may_load(deps.storage, &token_id)?;
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let (reservation, mut holds) = load_reservation(deps.as_ref(), &env, &config, &token_id)?;
+
+    // Nothing to wait for if the book is on the shelf
+    if loan.is_none() && reservation.is_none() {
+        return Err(ContractError::BookAvailable {});
+    }
+
+    let already_waiting = loan.map_or(false, |l| l.borrower == info.sender)
+        || reservation.as_ref().map_or(false, |r| r.holder == info.sender)
+        || holds.iter().any(|h| h.holder == info.sender);
+    if already_waiting {
+        return Err(ContractError::AlreadyOnHold {});
+    }
+    if holds.len() >= MAX_HOLDS {
+        return Err(ContractError::HoldQueueFull { max: MAX_HOLDS });
+    }
+
+    holds.push(Hold {
+        holder: info.sender.clone(),
+        placed_at: env.block.time,
+    });
+    save_reservation(deps.storage, &token_id, reservation.as_ref(), &holds)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "place_hold")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("position", holds.len().to_string()))
+}
+
+/// Removes the sender from the queue of a book, giving up its reservation if it holds one
+fn execute_cancel_hold(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let (mut reservation, mut holds) = load_reservation(deps.as_ref(), &env, &config, &token_id)?;
+
+    if reservation.as_ref().map_or(false, |r| r.holder == info.sender) {
+        // The next holder in line gets a fresh pickup window
+        reservation = if holds.is_empty() {
+            None
+        } else {
+            let next = holds.remove(0);
+            Some(Reservation {
+                holder: next.holder,
+                expires: config.pickup_window.after(&env.block),
+            })
+        };
+    } else {
+        let position = holds
+            .iter()
+            .position(|h| h.holder == info.sender)
+            .ok_or(ContractError::HoldNotFound {})?;
+        holds.remove(position);
+    }
+    save_reservation(deps.storage, &token_id, reservation.as_ref(), &holds)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_hold")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+// Helper function to load the active reservation and the remaining queue of a book.
+// Reservations whose pickup window has passed fall to the next holder in line, whose
+// window starts when the previous one ended.
+fn load_reservation(
+    deps: Deps,
+    env: &Env,
+    config: &LoanConfig,
+    token_id: &str,
+) -> StdResult<(Option<Reservation>, Vec<Hold>)> {
+    let mut reservation = RESERVATIONS.may_load(deps.storage, token_id)?;
+    let mut holds = HOLDS.may_load(deps.storage, token_id)?.unwrap_or_default();
+
+    while let Some(current) = reservation.take() {
+        if !current.expires.is_expired(&env.block) {
+            reservation = Some(current);
+            break;
+        }
+        if holds.is_empty() {
+            break;
+        }
+        let next = holds.remove(0);
+        let expires = (current.expires + config.pickup_window)
+            .unwrap_or_else(|_| config.pickup_window.after(&env.block));
+        reservation = Some(Reservation {
+            holder: next.holder,
+            expires,
+        });
+    }
+
+    Ok((reservation, holds))
+}
+
+// Helper function to store the reservation and the queue of a book
+fn save_reservation(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    reservation: Option<&Reservation>,
+    holds: &[Hold],
+) -> StdResult<()> {
+    match reservation {
+        Some(reservation) => RESERVATIONS.save(storage, token_id, reservation)?,
+        None => RESERVATIONS.remove(storage, token_id),
+    }
+    if holds.is_empty() {
+        HOLDS.remove(storage, token_id);
+    } else {
+        HOLDS.save(storage, token_id, &holds.to_vec())?;
+    }
+    Ok(())
+}
+
+// MIGRATION
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let (from_version, applied) =
+        migrations::migrate(deps, &env, &msg, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // Upgrades without storage changes apply no step, attribute values can't be empty
+    let steps_attr = if applied.is_empty() {
+        "none".to_string()
+    } else {
+        applied.join(";")
+    };
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", from_version)
+        .add_attribute("to_version", CONTRACT_VERSION)
+        .add_attribute("steps", steps_attr))
+}
+
+// QUERY HANDLERS
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        // CW721 standard queries
+        QueryMsg::OwnerOf {
+            token_id,
+            include_expired,
+        } => to_json_binary(&query_owner_of(deps, env, token_id, include_expired)?),
+        QueryMsg::Approval {
+            token_id,
+            spender,
+            include_expired,
+        } => to_json_binary(&query_approval(deps, env, token_id, spender, include_expired)?),
+        QueryMsg::Approvals {
+            token_id,
+            include_expired,
+        } => to_json_binary(&query_approvals(deps, env, token_id, include_expired)?),
+        QueryMsg::AllOperators {
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        } => to_json_binary(&query_all_operators(
+            deps,
+            env,
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::NumTokens {} => to_json_binary(&query_num_tokens(deps)?),
+        QueryMsg::NftInfo { token_id } => to_json_binary(&query_nft_info(deps, token_id)?),
+        QueryMsg::AllNftInfo {
+            token_id,
+            include_expired,
+        } => to_json_binary(&query_all_nft_info(deps, env, token_id, include_expired)?),
+        QueryMsg::Tokens {
+            owner,
+            start_after,
+            limit,
+        } => to_json_binary(&query_tokens(deps, owner, start_after, limit)?),
+        QueryMsg::AllTokens { start_after, limit } => {
+            to_json_binary(&query_all_tokens(deps, start_after, limit)?)
+        }
+        QueryMsg::ContractInfo {} => to_json_binary(&query_contract_info(deps)?),
+        QueryMsg::Minter {} => to_json_binary(&query_minter(deps)?),
+        QueryMsg::Ownership {} => to_json_binary(&ownership::query_ownership(deps)?),
+        QueryMsg::RoleHolders {
+            role,
+            start_after,
+            limit,
+        } => to_json_binary(&query_role_holders(deps, role, start_after, limit)?),
+        QueryMsg::Roles { address } => to_json_binary(&query_roles(deps, address)?),
+        QueryMsg::RolePolicy {} => to_json_binary(&query_role_policy(deps)?),
+        QueryMsg::PauseStatus {} => to_json_binary(&query_pause_status(deps)?),
+        
+        // Legacy queries for backward compatibility
+        QueryMsg::GetBorrower { token_id } => {
+            let borrower = loans().may_load(deps.storage, &token_id)?.map(|loan| loan.borrower);
+            to_json_binary(&borrower)
+        }
+        QueryMsg::GetBook { token_id } => to_json_binary(&query_book(deps, token_id)?),
+        QueryMsg::GetAllBooks { start_after, limit } => {
+            to_json_binary(&query_all_books(deps, start_after, limit)?)
+        }
+        QueryMsg::GetBorrowedBooks { start_after, limit } => {
+            to_json_binary(&query_borrowed_books(deps, start_after, limit)?)
+        }
+        QueryMsg::GetMyBorrowedBooks {
+            borrower,
+            start_after,
+            limit,
+        } => to_json_binary(&query_my_borrowed_books(deps, borrower, start_after, limit)?),
+        QueryMsg::GetAvailableBooks { start_after, limit } => {
+            to_json_binary(&query_available_books(deps, start_after, limit)?)
+        }
+        QueryMsg::GetLoan { token_id } => to_json_binary(&query_loan(deps, env, token_id)?),
+        QueryMsg::GetOverdueLoans { start_after, limit } => {
+            to_json_binary(&query_overdue_loans(deps, env, start_after, limit)?)
+        }
+        QueryMsg::BorrowDelegates {
+            borrower,
+            include_expired,
+            start_after,
+            limit,
+        } => to_json_binary(&query_borrow_delegates(
+            deps,
+            env,
+            borrower,
+            include_expired,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::BorrowRequestsByOwner {
+            owner,
+            start_after,
+            limit,
+        } => to_json_binary(&query_borrow_requests_by_owner(deps, owner, start_after, limit)?),
+        QueryMsg::BorrowRequestsByRequester {
+            requester,
+            start_after,
+            limit,
+        } => to_json_binary(&query_borrow_requests_by_requester(
+            deps,
+            requester,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetHolds { token_id } => to_json_binary(&query_holds(deps, env, token_id)?),
+        QueryMsg::LoanConfig {} => to_json_binary(&LOAN_CONFIG.load(deps.storage)?),
+        QueryMsg::Escrow {} => to_json_binary(&query_escrow(deps)?),
+        QueryMsg::GetDeposits { start_after, limit } => {
+            to_json_binary(&query_deposits(deps, env, start_after, limit)?)
+        }
+        QueryMsg::FeeConfig {} => {
+            to_json_binary(&FEE_CONFIG.may_load(deps.storage)?.unwrap_or_default())
+        }
+        QueryMsg::GetOutstandingFees { address } => {
+            to_json_binary(&query_outstanding_fees(deps, address)?)
+        }
+        QueryMsg::AcceptedCw20 {} => to_json_binary(&query_accepted_cw20(deps)?),
+        QueryMsg::Treasury {} => to_json_binary(&query_treasury(deps)?),
+        QueryMsg::GetRoyaltyInfo { token_id } => to_json_binary(&query_royalty_info(deps, token_id)?),
+        QueryMsg::Extension { msg } => to_json_binary(&cw2981::query_royalties(deps, msg)?),
+    }
+}
+
+fn query_owner_of(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: Option<bool>,
+) -> StdResult<OwnerOfResponse> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    
+    let include_expired = include_expired.unwrap_or(false);
+    
+    // Filter out expired approvals
+    let approvals: Vec<_> = token
+        .approvals
+        .iter()
+        .filter(|a| include_expired || !a.expires.is_expired(&env.block))
+        .map(|a| Approval {
+            spender: a.spender.to_string(),
+            expires: a.expires,
+        })
+        .collect();
+
+    Ok(OwnerOfResponse {
+        owner: token.owner.to_string(),
+        approvals,
+    })
+}
+
+fn query_approval(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    spender: String,
+    include_expired: Option<bool>,
+) -> StdResult<ApprovalResponse> {
+    let token = tokens().load(deps.storage, &token_id)?;
     let include_expired = include_expired.unwrap_or(false);
     
     // Find the approval for this spender
@@ -591,7 +2511,7 @@ fn query_approvals(
     token_id: String,
     include_expired: Option<bool>,
 ) -> StdResult<ApprovalsResponse> {
-    let token = TOKENS.load(deps.storage, &token_id)?;
+    let token = tokens().load(deps.storage, &token_id)?;
     let include_expired = include_expired.unwrap_or(false);
     
     // Filter expired approvals if requested
@@ -632,7 +2552,7 @@ fn query_all_operators(
         .prefix(&owner_addr)
         .range(
             deps.storage,
-            start_addr.as_ref().map(|x| Bound::exclusive(x)),
+            start_addr.as_ref().map(Bound::exclusive),
             None,
             Order::Ascending,
         )
@@ -659,7 +2579,7 @@ fn query_num_tokens(deps: Deps) -> StdResult<NumTokensResponse> {
 }
 
 fn query_nft_info(deps: Deps, token_id: String) -> StdResult<NftInfoResponse<Metadata>> {
-    let token = TOKENS.load(deps.storage, &token_id)?;
+    let token = tokens().load(deps.storage, &token_id)?;
     Ok(NftInfoResponse {
         token_uri: None, // We store metadata on-chain instead of URI
         extension: token.metadata,
@@ -684,38 +2604,18 @@ fn query_tokens(
     limit: Option<u32>,
 ) -> StdResult<TokensResponse> {
     let owner_addr = deps.api.addr_validate(&owner)?;
-    
+
     // Default and max limits for query pagination
     let limit = limit.unwrap_or(10).min(30) as usize;
-    
-    // Different approach to avoid Bound issues
-    let tokens = if let Some(start) = start_after {
-        TOKENS
-            .range(deps.storage, None, None, Order::Ascending)
-            .filter(|item| {
-                let (id, token) = item.as_ref().unwrap();
-                &start < id && token.owner == owner_addr
-            })
-            .take(limit)
-            .map(|item| {
-                let (token_id, _) = item?;
-                Ok(token_id)
-            })
-            .collect::<StdResult<Vec<String>>>()?
-    } else {
-        TOKENS
-            .range(deps.storage, None, None, Order::Ascending)
-            .filter(|item| {
-                let (_, token) = item.as_ref().unwrap();
-                token.owner == owner_addr
-            })
-            .take(limit)
-            .map(|item| {
-                let (token_id, _) = item?;
-                Ok(token_id)
-            })
-            .collect::<StdResult<Vec<String>>>()?
-    };
+    let start = start_after.map(Bound::exclusive);
+
+    let tokens = tokens()
+        .idx
+        .owner
+        .prefix(owner_addr)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
 
     Ok(TokensResponse { tokens })
 }
@@ -727,31 +2627,12 @@ fn query_all_tokens(
 ) -> StdResult<TokensResponse> {
     // Default and max limits for query pagination
     let limit = limit.unwrap_or(10).min(30) as usize;
-    
-    // Different approach to avoid Bound issues
-    let tokens = if let Some(start) = start_after {
-        TOKENS
-            .range(deps.storage, None, None, Order::Ascending)
-            .filter(|item| {
-                let (id, _) = item.as_ref().unwrap();
-                &start < id
-            })
-            .take(limit)
-            .map(|item| {
-                let (token_id, _) = item?;
-                Ok(token_id)
-            })
-            .collect::<StdResult<Vec<String>>>()?
-    } else {
-        TOKENS
-            .range(deps.storage, None, None, Order::Ascending)
-            .take(limit)
-            .map(|item| {
-                let (token_id, _) = item?;
-                Ok(token_id)
-            })
-            .collect::<StdResult<Vec<String>>>()?
-    };
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let tokens = tokens()
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
 
     Ok(TokensResponse { tokens })
 }
@@ -760,77 +2641,473 @@ fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
     CONTRACT_INFO.load(deps.storage)
 }
 
+fn query_minter(deps: Deps) -> StdResult<MinterResponse> {
+    let minter = MINTER.load(deps.storage)?;
+    Ok(MinterResponse {
+        minter: minter.to_string(),
+    })
+}
+
+fn query_role_holders(
+    deps: Deps,
+    role: Role,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RoleHoldersResponse> {
+    let start_addr = if let Some(start) = start_after {
+        Some(deps.api.addr_validate(&start)?)
+    } else {
+        None
+    };
+
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+
+    let holders = ROLES
+        .prefix(role.as_str())
+        .keys(
+            deps.storage,
+            start_addr.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|addr| addr.to_string()))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RoleHoldersResponse { holders })
+}
+
+fn query_roles(deps: Deps, address: String) -> StdResult<RolesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+
+    let mut roles = vec![];
+    for role in Role::ALL {
+        if has_role(deps, role, &address)? {
+            roles.push(role);
+        }
+    }
+
+    Ok(RolesResponse { roles })
+}
+
+fn query_role_policy(deps: Deps) -> StdResult<RolePolicyResponse> {
+    let policies = POLICY_ACTIONS
+        .iter()
+        .map(|action| {
+            let roles = ROLE_POLICY.may_load(deps.storage, action)?.unwrap_or_default();
+            Ok(ActionPolicy {
+                action: action.to_string(),
+                roles,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RolePolicyResponse { policies })
+}
+
+fn query_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+    let paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PauseStatusResponse { paused })
+}
+
 // Legacy query implementations
 
-/// Retrieves details of a specific book by its token ID
-fn query_book(deps: Deps, token_id: String) -> StdResult<Binary> {
-    let book = BOOKS.load(deps.storage, &token_id)?;
-    to_json_binary(&book)
+/// Retrieves details of a specific book by its token ID, together with its borrower
+fn query_book(deps: Deps, token_id: String) -> StdResult<BookResponse> {
+    let book: Book = tokens().load(deps.storage, &token_id)?.into();
+    let borrower = loans().may_load(deps.storage, &token_id)?.map(|loan| loan.borrower);
+    Ok(BookResponse {
+        token_id,
+        book,
+        borrower,
+    })
 }
 
-/// Retrieves details of all books in the library.
-fn query_all_books(deps: Deps) -> StdResult<Binary> {
-    // Use a more gas-efficient approach by limiting the number of books returned
-    const MAX_BOOKS: usize = 100;
-    let mut books = Vec::with_capacity(MAX_BOOKS);
-    
-    for item in BOOKS.range(deps.storage, None, None, Order::Ascending).take(MAX_BOOKS) {
-        let (key, book) = item?;
-        books.push((key, book));
+// Legacy book lists return at most this many books per page
+const DEFAULT_BOOKS_LIMIT: u32 = 30;
+const MAX_BOOKS_LIMIT: u32 = 100;
+
+// Helper function to cut a page of books one past the limit, the extra book only tells
+// whether there is a next page
+fn books_page(mut books: Vec<BookResponse>, limit: usize) -> BooksResponse {
+    let next_start_after = if books.len() > limit {
+        books.truncate(limit);
+        books.last().map(|book| book.token_id.clone())
+    } else {
+        None
+    };
+    BooksResponse {
+        books,
+        next_start_after,
     }
+}
+
+/// Retrieves details of all books in the library
+fn query_all_books(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BooksResponse> {
+    let limit = limit.unwrap_or(DEFAULT_BOOKS_LIMIT).min(MAX_BOOKS_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
 
-    to_json_binary(&books)
+    let books = tokens()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            let (token_id, token) = item?;
+            let borrower = loans().may_load(deps.storage, &token_id)?.map(|loan| loan.borrower);
+            Ok(BookResponse {
+                token_id,
+                book: Book::from(token),
+                borrower,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(books_page(books, limit))
 }
 
 /// Retrieves details of all borrowed books in the library
-fn query_borrowed_books(deps: Deps) -> StdResult<Binary> {
-    const MAX_BOOKS: usize = 100;
-    let mut borrowed_books = Vec::with_capacity(MAX_BOOKS);
-    
-    for item in BORROWERS.range(deps.storage, None, None, Order::Ascending).take(MAX_BOOKS) {
-        let (token_id, borrower) = item?;
-        if let Ok(book) = BOOKS.load(deps.storage, &token_id) {
-            borrowed_books.push((token_id, book, borrower));
-        }
-    }
+fn query_borrowed_books(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BooksResponse> {
+    let limit = limit.unwrap_or(DEFAULT_BOOKS_LIMIT).min(MAX_BOOKS_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let books = loans()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            let (token_id, loan) = item?;
+            let token = tokens().load(deps.storage, &token_id)?;
+            Ok(BookResponse {
+                token_id,
+                book: Book::from(token),
+                borrower: Some(loan.borrower),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
 
-    to_json_binary(&borrowed_books)
+    Ok(books_page(books, limit))
 }
 
 /// Retrieves details of all books borrowed by a specific address
-fn query_my_borrowed_books(deps: Deps, borrower: Addr) -> StdResult<Binary> {
-    const MAX_BOOKS: usize = 100;
-    let mut my_borrowed_books = Vec::with_capacity(MAX_BOOKS);
-    
-    for item in BORROWERS.range(deps.storage, None, None, Order::Ascending).take(MAX_BOOKS) {
-        let (token_id, current_borrower) = item?;
-        if current_borrower == borrower {
-            if let Ok(book) = BOOKS.load(deps.storage, &token_id) {
-                my_borrowed_books.push((token_id, book));
-            }
-        }
-    }
+fn query_my_borrowed_books(
+    deps: Deps,
+    borrower: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BooksResponse> {
+    let limit = limit.unwrap_or(DEFAULT_BOOKS_LIMIT).min(MAX_BOOKS_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let books = loans()
+        .idx
+        .borrower
+        .prefix(borrower.clone())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            let token_id = item?;
+            let token = tokens().load(deps.storage, &token_id)?;
+            Ok(BookResponse {
+                token_id,
+                book: Book::from(token),
+                borrower: Some(borrower.clone()),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
 
-    to_json_binary(&my_borrowed_books)
+    Ok(books_page(books, limit))
 }
 
 /// Retrieves details of all books that are available for borrowing (not currently borrowed)
-fn query_available_books(deps: Deps) -> StdResult<Binary> {
-    const MAX_BOOKS: usize = 100;
-    let mut available_books = Vec::with_capacity(MAX_BOOKS);
-    
-    // Get all books
-    for item in BOOKS.range(deps.storage, None, None, Order::Ascending).take(MAX_BOOKS) {
-        let (token_id, book) = item?;
-        
-        // Check if the book is currently borrowed
-        let is_borrowed = BORROWERS.may_load(deps.storage, &token_id)?.is_some();
-        
-        // If not borrowed, add to available books
-        if !is_borrowed {
-            available_books.push((token_id, book));
+fn query_available_books(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BooksResponse> {
+    let limit = limit.unwrap_or(DEFAULT_BOOKS_LIMIT).min(MAX_BOOKS_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let books = tokens()
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((token_id, _)) => !loans().has(deps.storage, token_id),
+            Err(_) => true,
+        })
+        .take(limit + 1)
+        .map(|item| {
+            let (token_id, token) = item?;
+            Ok(BookResponse {
+                token_id,
+                book: Book::from(token),
+                borrower: None,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(books_page(books, limit))
+}
+
+/// Retrieves the current loan of a book, evaluated against the current block
+fn query_loan(deps: Deps, env: Env, token_id: String) -> StdResult<Option<LoanResponse>> {
+    let loan = loans().may_load(deps.storage, &token_id)?;
+    loan.map(|loan| loan_response(&env, token_id, loan)).transpose()
+}
+
+/// Retrieves the loans past their due date
+fn query_overdue_loans(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<LoansResponse> {
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let loans = loans()
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, loan)) => loan.due.is_expired(&env.block),
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| {
+            let (token_id, loan) = item?;
+            loan_response(&env, token_id, loan)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(LoansResponse { loans })
+}
+
+fn loan_response(env: &Env, token_id: String, loan: Loan) -> StdResult<LoanResponse> {
+    let late_fee = loan
+        .late_fee
+        .as_ref()
+        .and_then(|late_fee| accrued_late_fee(late_fee, &loan.due, &env.block));
+    Ok(LoanResponse {
+        token_id,
+        late_fee,
+        overdue: loan.due.is_expired(&env.block),
+        borrower: loan.borrower,
+        start_time: loan.start_time,
+        start_height: loan.start_height,
+        due: loan.due,
+        renewals: loan.renewals,
+        deposit: loan.deposit,
+    })
+}
+
+/// Retrieves the reservation and the queue of holders of a book, evaluated against the current block
+fn query_holds(deps: Deps, env: Env, token_id: String) -> StdResult<HoldsResponse> {
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let (reservation, holds) = load_reservation(deps, &env, &config, &token_id)?;
+    Ok(HoldsResponse { reservation, holds })
+}
+
+/// Retrieves the delegates allowed to borrow on behalf of a borrower
+fn query_borrow_delegates(
+    deps: Deps,
+    env: Env,
+    borrower: String,
+    include_expired: Option<bool>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OperatorsResponse> {
+    let borrower_addr = deps.api.addr_validate(&borrower)?;
+    let include_expired = include_expired.unwrap_or(false);
+
+    let start_addr = if let Some(start) = start_after {
+        Some(deps.api.addr_validate(&start)?)
+    } else {
+        None
+    };
+
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+
+    let operators = BORROW_DELEGATES
+        .prefix(&borrower_addr)
+        .range(
+            deps.storage,
+            start_addr.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .filter(|item| match item {
+            Ok((_, expires)) => include_expired || !expires.is_expired(&env.block),
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| {
+            let (addr, expires) = item?;
+            Ok(Approval {
+                spender: addr.to_string(),
+                expires,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(OperatorsResponse { operators })
+}
+
+/// Retrieves the pending borrow requests for books of an owner
+fn query_borrow_requests_by_owner(
+    deps: Deps,
+    owner: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<BorrowRequestsResponse> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+
+    let requests = borrow_requests()
+        .idx
+        .owner
+        .prefix(owner_addr)
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, request)| borrow_request_response(id, request)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(BorrowRequestsResponse { requests })
+}
+
+/// Retrieves the pending borrow requests made by a requester
+fn query_borrow_requests_by_requester(
+    deps: Deps,
+    requester: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<BorrowRequestsResponse> {
+    let requester_addr = deps.api.addr_validate(&requester)?;
+
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+
+    let requests = borrow_requests()
+        .idx
+        .requester
+        .prefix(requester_addr)
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, request)| borrow_request_response(id, request)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(BorrowRequestsResponse { requests })
+}
+
+fn borrow_request_response(request_id: u64, request: BorrowRequest) -> BorrowRequestResponse {
+    BorrowRequestResponse {
+        request_id,
+        token_id: request.token_id,
+        requester: request.requester,
+        owner: request.owner,
+        created_at: request.created_at,
+        payment: request.payment,
+        deposit: request.deposit,
+    }
+}
+
+/// Retrieves the total deposits held in escrow, per denom
+fn query_escrow(deps: Deps) -> StdResult<EscrowResponse> {
+    let deposits = ESCROWED_DEPOSITS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(EscrowResponse { deposits })
+}
+
+/// Retrieves the loans holding a deposit
+fn query_deposits(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<LoansResponse> {
+    // Default and max limits for query pagination
+    let limit = limit.unwrap_or(10).min(30) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let loans = loans()
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, loan)) => loan.deposit.is_some(),
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| {
+            let (token_id, loan) = item?;
+            loan_response(&env, token_id, loan)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(LoansResponse { loans })
+}
+
+/// Retrieves the late fees an address left unpaid
+fn query_outstanding_fees(deps: Deps, address: String) -> StdResult<OutstandingFeesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let fees = OUTSTANDING_FEES
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(token_id, fee)| OutstandingFeeResponse {
+                token_id,
+                owner: fee.owner,
+                amount: fee.amount,
+                incurred_at: fee.incurred_at,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut total: Vec<Coin> = vec![];
+    for fee in &fees {
+        match total.iter_mut().find(|c| c.denom == fee.amount.denom) {
+            Some(c) => c.amount += fee.amount.amount,
+            None => total.push(fee.amount.clone()),
         }
     }
 
-    to_json_binary(&available_books)
-}
\ No newline at end of file
+    Ok(OutstandingFeesResponse { fees, total })
+}
+
+/// Retrieves the CW20 contracts accepted as payment
+fn query_accepted_cw20(deps: Deps) -> StdResult<AcceptedCw20Response> {
+    let tokens = ACCEPTED_CW20
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(AcceptedCw20Response { tokens })
+}
+
+/// Retrieves the platform fees collected and not yet withdrawn, per denom
+fn query_treasury(deps: Deps) -> StdResult<TreasuryResponse> {
+    let balances = TREASURY
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(TreasuryResponse { balances })
+}
+
+/// Retrieves how the borrow fees and tips of a book are shared
+fn query_royalty_info(deps: Deps, token_id: String) -> StdResult<RoyaltyInfoResponse> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    let shares = revenue_shares(deps.storage, &token)?;
+    Ok(RoyaltyInfoResponse {
+        token_id,
+        owner: token.owner,
+        author: shares.author,
+        owner_bps: shares.owner_bps,
+        author_bps: shares.author_bps,
+        platform_bps: shares.platform_bps,
+    })
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::{Item, Map};
+use cw2::{get_contract_version, set_contract_version, ContractVersion};
+use cw721::ContractInfoResponse;
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::msg::MigrateMsg;
+use crate::ownership;
+use crate::state::{
+    borrow_requests, Loan, LoanConfig, Role, BOOKS, CONTRACT_INFO, ESCROWED_DEPOSITS, FEE_CONFIG,
+    loans, LOAN_CONFIG, MINTER, PENDING_PAYMENTS, NUM_TOKENS, ROLE_POLICY, STAFF_ACTIONS, tokens, TREASURY,
+};
+
+// BORROWERS layout before 0.4.0: the borrower address of each book
+const LEGACY_BORROWERS: Map<&str, Addr> = Map::new("borrowers");
+
+// CONTRACT_INFO key in 0.1.0, shared with cw2 so instantiation overwrote the stored version
+const LEGACY_CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("contract_info");
+
+// A storage upgrade applied when migrating from a version older than `version`
+struct MigrationStep {
+    version: &'static str,
+    description: &'static str,
+    run: fn(DepsMut, &Env, &MigrateMsg) -> Result<(), ContractError>,
+}
+
+// Ordered list of storage upgrades, each one is applied at most once.
+// Steps receive the whole storage so they can rewrite TOKENS, BOOKS and BORROWERS as needed.
+const MIGRATION_STEPS: &[MigrationStep] = &[
+    MigrationStep {
+        version: "0.2.0",
+        description: "store minter, ownership and default role policy",
+        run: v0_2_0_init_admin_state,
+    },
+    MigrationStep {
+        version: "0.3.0",
+        description: "merge legacy books into tokens",
+        run: v0_3_0_merge_books_into_tokens,
+    },
+    MigrationStep {
+        version: "0.4.0",
+        description: "turn borrowers into loans with due dates",
+        run: v0_4_0_borrowers_into_loans,
+    },
+    MigrationStep {
+        version: "0.5.0",
+        description: "credit collected platform fees to the treasury",
+        run: v0_5_0_init_treasury,
+    },
+    MigrationStep {
+        version: "0.6.0",
+        description: "index tokens by owner",
+        run: v0_6_0_index_tokens_by_owner,
+    },
+    MigrationStep {
+        version: "0.7.0",
+        description: "index loans by borrower",
+        run: v0_7_0_index_loans_by_borrower,
+    },
+];
+
+/// Checks the stored cw2 version and applies every pending migration step in order.
+/// Returns the version migrated from and the descriptions of the applied steps.
+pub fn migrate(
+    mut deps: DepsMut,
+    env: &Env,
+    msg: &MigrateMsg,
+    contract_name: &str,
+    contract_version: &str,
+) -> Result<(String, Vec<&'static str>), ContractError> {
+    let stored = match get_contract_version(deps.storage) {
+        Ok(stored) => stored,
+        Err(_) => recover_contract_version(deps.storage, msg, contract_name)?,
+    };
+
+    // Refuse to take over the storage of another contract
+    if stored.contract != contract_name {
+        return Err(ContractError::ForeignContract {
+            contract: stored.contract,
+        });
+    }
+
+    // Refuse downgrades, the storage may already use a newer layout
+    let stored_version: Version = stored.version.parse()?;
+    let new_version: Version = contract_version.parse()?;
+    if stored_version > new_version {
+        return Err(ContractError::Downgrade {
+            stored: stored.version,
+            new: contract_version.to_string(),
+        });
+    }
+
+    let mut applied = vec![];
+    for step in MIGRATION_STEPS {
+        let step_version: Version = step.version.parse()?;
+        if stored_version < step_version && step_version <= new_version {
+            (step.run)(deps.branch(), env, msg)?;
+            applied.push(step.description);
+        }
+    }
+
+    set_contract_version(deps.storage, contract_name, contract_version)?;
+
+    Ok((stored.version, applied))
+}
+
+// 0.1.0 saved the contract info over the cw2 version, it is moved to its own key
+// and the version it replaced is taken from the migrate message
+fn recover_contract_version(
+    storage: &mut dyn Storage,
+    msg: &MigrateMsg,
+    contract_name: &str,
+) -> Result<ContractVersion, ContractError> {
+    let contract_info = LEGACY_CONTRACT_INFO.load(storage)?;
+    let version = msg.from_version.clone().ok_or(ContractError::MissingMigrateField {
+        field: "from_version".to_string(),
+    })?;
+    CONTRACT_INFO.save(storage, &contract_info)?;
+    Ok(ContractVersion {
+        contract: contract_name.to_string(),
+        version,
+    })
+}
+
+// 0.1.0 validated the minter without storing it and had no notion of ownership or roles
+fn v0_2_0_init_admin_state(
+    deps: DepsMut,
+    _env: &Env,
+    msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    if !ownership::is_initialized(deps.storage) {
+        let owner = msg.owner.as_ref().ok_or(ContractError::MissingMigrateField {
+            field: "owner".to_string(),
+        })?;
+        let owner = deps.api.addr_validate(owner)?;
+        ownership::initialize_owner(deps.storage, &owner)?;
+    }
+
+    if !MINTER.exists(deps.storage) {
+        // Default the minter to the contract owner
+        let minter = match &msg.minter {
+            Some(minter) => deps.api.addr_validate(minter)?,
+            None => ownership::get_ownership(deps.storage)?
+                .owner
+                .ok_or(ContractError::MissingMigrateField {
+                    field: "minter".to_string(),
+                })?,
+        };
+        MINTER.save(deps.storage, &minter)?;
+    }
+
+    for action in STAFF_ACTIONS {
+        if !ROLE_POLICY.has(deps.storage, action) {
+            ROLE_POLICY.save(deps.storage, action, &vec![Role::Admin, Role::Librarian])?;
+        }
+    }
+
+    Ok(())
+}
+
+// Up to 0.2.x AddBook wrote both BOOKS and TOKENS while Mint only wrote TOKENS, and Burn
+// removed the TOKENS entry only. TOKENS becomes the single source of truth: BOOKS is cleared,
+// its entries without a token being burned books, loans of unknown tokens are dropped and
+// NUM_TOKENS is recounted.
+fn v0_3_0_merge_books_into_tokens(
+    deps: DepsMut,
+    _env: &Env,
+    _msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    let books = BOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for token_id in books {
+        BOOKS.remove(deps.storage, &token_id);
+    }
+
+    // Borrowers were still stored as plain addresses at this version
+    let loans = LEGACY_BORROWERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for token_id in loans {
+        if !tokens().has(deps.storage, &token_id) {
+            LEGACY_BORROWERS.remove(deps.storage, &token_id);
+        }
+    }
+
+    let count = tokens()
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count() as u64;
+    NUM_TOKENS.save(deps.storage, &count)?;
+
+    Ok(())
+}
+
+// Before 0.4.0 a loan was only the borrower address. Existing loans start now and are due
+// after the default loan period (or the book's own, if set), with the late fee of the book.
+fn v0_4_0_borrowers_into_loans(
+    deps: DepsMut,
+    env: &Env,
+    _msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    if !LOAN_CONFIG.exists(deps.storage) {
+        LOAN_CONFIG.save(deps.storage, &LoanConfig::default())?;
+    }
+    let config = LOAN_CONFIG.load(deps.storage)?;
+    let default_fee = FEE_CONFIG.may_load(deps.storage)?.and_then(|config| config.late_fee);
+
+    let borrowers = LEGACY_BORROWERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for (token_id, borrower) in borrowers {
+        let terms = tokens()
+            .may_load(deps.storage, &token_id)?
+            .map(|token| token.terms)
+            .unwrap_or_default();
+        let loan_period = terms.loan_period.unwrap_or(config.default_loan_period);
+        let loan = Loan {
+            borrower,
+            start_time: env.block.time,
+            start_height: env.block.height,
+            due: loan_period.after(&env.block),
+            renewals: 0,
+            deposit: None,
+            late_fee: terms.late_fee.or_else(|| default_fee.clone()),
+        };
+        // The stored value is still a plain address, it must not be read back as the old loan
+        loans().replace(deps.storage, &token_id, Some(&loan), None)?;
+    }
+
+    Ok(())
+}
+
+// Before 0.5.0 platform fees stayed in the contract balance without being tracked, nor were
+// the funds held for borrowers totalled. The deposits and borrow fees of loans and pending
+// requests are totalled and the native balance they don't account for is credited to the
+// treasury. CW20 balances can't be listed and start from zero.
+fn v0_5_0_init_treasury(deps: DepsMut, env: &Env, _msg: &MigrateMsg) -> Result<(), ContractError> {
+    let pending = pending_payments(deps.storage)?;
+    for (denom, total) in &pending {
+        PENDING_PAYMENTS.save(deps.storage, denom, total)?;
+    }
+    // Deposits of loans made without a request were left out of the escrow total
+    let stale = ESCROWED_DEPOSITS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in stale {
+        ESCROWED_DEPOSITS.remove(deps.storage, &denom);
+    }
+    let escrowed = escrowed_deposits(deps.storage)?;
+    for (denom, total) in &escrowed {
+        ESCROWED_DEPOSITS.save(deps.storage, denom, total)?;
+    }
+
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    for coin in balances {
+        let held = escrowed.get(&coin.denom).copied().unwrap_or_default()
+            + pending.get(&coin.denom).copied().unwrap_or_default();
+        let collected = coin.amount.saturating_sub(held);
+        if !collected.is_zero() {
+            TREASURY.save(deps.storage, &coin.denom, &collected)?;
+        }
+    }
+    Ok(())
+}
+
+// Before 0.6.0 tokens were a plain map. The data stays under the same namespace, saving every
+// token again through the indexed map writes its owner index entry.
+fn v0_6_0_index_tokens_by_owner(
+    deps: DepsMut,
+    _env: &Env,
+    _msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    let all = tokens()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (token_id, token) in all {
+        tokens().replace(deps.storage, &token_id, Some(&token), None)?;
+    }
+    Ok(())
+}
+
+// Before 0.7.0 loans were a plain map, they get their borrower index the same way as tokens
+fn v0_7_0_index_loans_by_borrower(
+    deps: DepsMut,
+    _env: &Env,
+    _msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    let all = loans()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (token_id, loan) in all {
+        loans().replace(deps.storage, &token_id, Some(&loan), None)?;
+    }
+    Ok(())
+}
+
+// Helper function to sum the deposits held for loans and pending requests, per denom
+fn escrowed_deposits(storage: &dyn Storage) -> StdResult<BTreeMap<String, Uint128>> {
+    let mut totals = BTreeMap::<String, Uint128>::new();
+    for item in loans().range(storage, None, None, Order::Ascending) {
+        let (_, loan) = item?;
+        if let Some(deposit) = loan.deposit {
+            *totals.entry(deposit.denom).or_default() += deposit.amount;
+        }
+    }
+    for item in borrow_requests().range(storage, None, None, Order::Ascending) {
+        let (_, request) = item?;
+        if let Some(deposit) = request.deposit {
+            *totals.entry(deposit.denom).or_default() += deposit.amount;
+        }
+    }
+    Ok(totals)
+}
+
+// Helper function to sum the borrow fees held with pending requests, per denom
+fn pending_payments(storage: &dyn Storage) -> StdResult<BTreeMap<String, Uint128>> {
+    let mut totals = BTreeMap::<String, Uint128>::new();
+    for item in borrow_requests().range(storage, None, None, Order::Ascending) {
+        let (_, request) = item?;
+        if let Some(payment) = request.payment {
+            *totals.entry(payment.denom).or_default() += payment.amount;
+        }
+    }
+    Ok(totals)
+}
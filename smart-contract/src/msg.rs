@@ -1,15 +1,53 @@
-use cosmwasm_std::{Addr, Binary};
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp};
+use cw_utils::Duration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cw721::{Expiration};
+use cw721::{
+    AllNftInfoResponse, ApprovalResponse, ApprovalsResponse, ContractInfoResponse, Expiration,
+    NftInfoResponse, NumTokensResponse, OperatorsResponse, OwnerOfResponse, TokensResponse,
+};
 
-use crate::state::Metadata;
+use crate::cw20::Cw20ReceiveMsg;
+use crate::cw2981::{Cw2981QueryMsg, Cw2981Response};
+use crate::ownership::Action;
+use crate::state::{Book, FeeConfig, Hold, LateFee, LendingPolicy, LoanConfig, Metadata, PauseCategory, Reservation, Role};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub name: String,
     pub symbol: String,
     pub minter: String,
+    /// Contract owner (admin), defaults to the instantiating account
+    pub owner: Option<String>,
+    /// Loan period for books without their own, defaults to two weeks
+    pub default_loan_period: Option<Duration>,
+    /// Maximum number of renewals per loan, defaults to 2
+    pub max_renewals: Option<u32>,
+    /// How long a returned book is reserved for the next holder, defaults to three days
+    pub pickup_window: Option<Duration>,
+    /// Share of borrow fees kept by the contract, in basis points, defaults to 0
+    pub platform_fee_bps: Option<u16>,
+    /// Late fee for books without their own, none by default
+    pub late_fee: Option<LateFee>,
+    /// How long after the due date a deposit can be claimed, defaults to one week.
+    /// Must count the same unit as the default loan period
+    pub deposit_grace_period: Option<Duration>,
+    /// Whether books on loan can be transferred along with their loan, defaults to false
+    pub transfer_loans: Option<bool>,
+}
+
+/// Parameters for state that older versions did not store, only used when
+/// migrating from a version that lacks it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    /// Contract owner, required when migrating from 0.1.x
+    pub owner: Option<String>,
+    /// Contract minter, defaults to the owner
+    pub minter: Option<String>,
+    /// Version the contract is migrated from, required when the contract info overwrote
+    /// the stored version, as in 0.1.0
+    pub from_version: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -19,7 +57,8 @@ pub enum ExecuteMsg {
     /// Transfer is a base message to move a token to another account without triggering actions
     TransferNft { recipient: String, token_id: String },
     /// Send is a base message to transfer a token to a contract and trigger an action
-    /// on the receiving contract.
+    /// on the receiving contract. The contract is called with `receive_nft { sender, token_id, msg }`.
+    /// Books on loan can't be sent
     SendNft {
         contract: String,
         token_id: String,
@@ -48,48 +87,199 @@ pub enum ExecuteMsg {
         owner: String,
         metadata: Metadata,
     },
-    /// Burn an NFT the sender has access to
+    /// Burn an NFT the sender has access to, books on loan can't be burned
     Burn { token_id: String },
+    /// Hand the minter role over to another address, can only be called by the current minter
+    UpdateMinter { new_minter: String },
+    /// Propose, accept or renounce the contract ownership (cw-ownable compatible)
+    UpdateOwnership(Action),
+    /// Grant a role to an address, can only be called by the owner or an admin
+    GrantRole { role: Role, address: String },
+    /// Revoke a role from an address, can only be called by the owner or an admin
+    RevokeRole { role: Role, address: String },
+    /// Set the roles allowed to perform an action, can only be called by the owner or an admin
+    SetRolePolicy { action: String, roles: Vec<Role> },
+    /// Freeze state-changing messages, every category if unset.
+    /// Can only be called by the owner or an admin
+    Pause { categories: Option<Vec<PauseCategory>> },
+    /// Resume paused messages, every category if unset.
+    /// Can only be called by the owner or an admin
+    Unpause { categories: Option<Vec<PauseCategory>> },
+    /// Update the lending configuration, unset fields are left unchanged.
+    /// Can only be called by the owner or an admin
+    UpdateLoanConfig {
+        default_loan_period: Option<Duration>,
+        max_renewals: Option<u32>,
+        pickup_window: Option<Duration>,
+        deposit_grace_period: Option<Duration>,
+        transfer_loans: Option<bool>,
+    },
+    /// Override the loan period of a book, can only be called by the book owner.
+    /// Unset falls back to the default loan period
+    SetLoanPeriod {
+        token_id: String,
+        loan_period: Option<Duration>,
+    },
 
     // Original library-specific messages
+    /// Add a book, the optional details are the same as in the book metadata
     AddBook {
         token_id: String,
         title: String,
         author: String,
         url: String,
         owner: Addr,
+        #[serde(default)]
+        co_authors: Vec<String>,
+        isbn: Option<String>,
+        publication_year: Option<u32>,
+        #[serde(default)]
+        genres: Vec<String>,
+        publisher: Option<String>,
+        language: Option<String>,
+        page_count: Option<u32>,
+        description: Option<String>,
+        image: Option<String>,
     },
+    /// Borrow a book for the sender
     BorrowBook {
         token_id: String,
-        borrower: Addr,
     },
+    /// Borrow a book on behalf of a borrower who approved the sender as a delegate
+    BorrowBookFor {
+        token_id: String,
+        borrower: String,
+    },
+    /// Allow a delegate to borrow books on the sender's behalf.
+    /// If expiration is set, then this allowance has a time/height limit
+    ApproveBorrowDelegate {
+        delegate: String,
+        expires: Option<Expiration>,
+    },
+    /// Remove a previously approved borrow delegate
+    RevokeBorrowDelegate { delegate: String },
     ReturnBook {
         token_id: String,
     },
+    /// Extend the due date of a loan by the loan period, can only be called by the borrower
+    RenewLoan {
+        token_id: String,
+    },
+    /// Set who can borrow a book, can only be called by the book owner
+    SetLendingPolicy {
+        token_id: String,
+        policy: LendingPolicy,
+    },
+    /// Set the fee charged for each loan of a book in a native denom, can only be called by the book owner.
+    /// Unset makes the book free to borrow
+    SetBorrowPrice {
+        token_id: String,
+        price: Option<Coin>,
+    },
+    /// Require borrowers to lock a refundable deposit, can only be called by the book owner.
+    /// Unset removes the requirement
+    SetDeposit {
+        token_id: String,
+        deposit: Option<Coin>,
+    },
+    /// Take the deposit of a loan overdue past the grace period, ending the loan.
+    /// Can only be called by the book owner
+    ClaimDeposit { token_id: String },
+    /// Charge a fee for late returns of a book instead of the contract-wide one,
+    /// can only be called by the book owner. Unset falls back to the contract-wide late fee
+    SetLateFee {
+        token_id: String,
+        late_fee: Option<LateFee>,
+    },
+    /// Pay late fees left unpaid at return with the attached funds, oldest books first
+    PayFees {},
+    /// Share the borrow fees and tips of a book between its owner, its author and the platform,
+    /// can only be called by the book owner. Unset pays the owner minus the contract-wide platform fee
+    SetRevenueSplit {
+        token_id: String,
+        split: Option<RevenueSplitMsg>,
+    },
+    /// Send the attached funds as a tip to the author of a book
+    TipAuthor { token_id: String },
+    /// Pay with a CW20 token sent through its `send` message, the inner message is a `ReceiveMsg`.
+    /// Only accepted CW20 contracts can call it
+    Receive(Cw20ReceiveMsg),
+    /// Send collected platform fees to a recipient, can only be called by the owner or an admin
+    WithdrawTreasury { amount: Coin, recipient: String },
+    /// Add or remove CW20 contracts accepted as payment, can only be called by the owner or an admin
+    UpdateAcceptedCw20 {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Set the share of borrow fees kept by the contract and the contract-wide late fee,
+    /// can only be called by the owner or an admin
+    UpdateFeeConfig {
+        platform_fee_bps: u16,
+        late_fee: Option<LateFee>,
+    },
+    /// Lend the book of a pending borrow request to its requester, can only be called by the book owner
+    AcceptBorrowRequest { request_id: u64 },
+    /// Turn down a pending borrow request, can only be called by the book owner
+    RejectBorrowRequest { request_id: u64 },
+    /// Withdraw a pending borrow request, can only be called by the requester
+    CancelBorrowRequest { request_id: u64 },
+    /// Join the queue of members waiting for a book that is borrowed or reserved
+    PlaceHold {
+        token_id: String,
+    },
+    /// Leave the queue for a book, or give up its reservation
+    CancelHold {
+        token_id: String,
+    },
+}
+
+/// Shares in basis points, they must sum to 10000
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RevenueSplitMsg {
+    pub author: String,
+    pub owner_bps: u16,
+    pub author_bps: u16,
+    pub platform_bps: u16,
 }
 
+/// Messages carried by a CW20 `send` to this contract, paid with the sent tokens
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Same as `ExecuteMsg::BorrowBook`, the price and deposit must both be in the sent token
+    BorrowBook { token_id: String },
+    /// Same as `ExecuteMsg::PayFees`
+    PayFees {},
+    /// Same as `ExecuteMsg::TipAuthor`
+    TipAuthor { token_id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     // CW721 standard queries
     /// Return the owner of the given token, error if token does not exist
+    #[returns(OwnerOfResponse)]
     OwnerOf {
         token_id: String,
         /// unset or false will filter out expired approvals, you must set to true to see them
         include_expired: Option<bool>,
     },
     /// Return operator that can access all of the owner's tokens
+    #[returns(ApprovalResponse)]
     Approval {
         token_id: String,
         spender: String,
         include_expired: Option<bool>,
     },
     /// Return approvals that a token has
+    #[returns(ApprovalsResponse)]
     Approvals {
         token_id: String,
         include_expired: Option<bool>,
     },
     /// List all operators that can access all of the owner's tokens
+    #[returns(OperatorsResponse)]
     AllOperators {
         owner: String,
         /// unset or false will filter out expired items, you must set to true to see them
@@ -98,14 +288,17 @@ pub enum QueryMsg {
         limit: Option<u32>,
     },
     /// Total number of tokens issued
+    #[returns(NumTokensResponse)]
     NumTokens {},
     /// With MetaData Extension
     /// Returns metadata about one particular token
+    #[returns(NftInfoResponse<Metadata>)]
     NftInfo {
         token_id: String,
     },
     /// With MetaData Extension
     /// Returns the result of both `NftInfo` and `OwnerOf` as one query as an optimization
+    #[returns(AllNftInfoResponse<Metadata>)]
     AllNftInfo {
         token_id: String,
         /// unset or false will filter out expired approvals, you must set to true to see them
@@ -113,6 +306,7 @@ pub enum QueryMsg {
     },
     /// With Enumerable extension
     /// Returns all tokens owned by the given address, [] if unset
+    #[returns(TokensResponse)]
     Tokens {
         owner: String,
         start_after: Option<String>,
@@ -120,20 +314,132 @@ pub enum QueryMsg {
     },
     /// With Enumerable extension
     /// Requires pagination. Lists all token_ids controlled by the contract.
+    #[returns(TokensResponse)]
     AllTokens {
         start_after: Option<String>,
         limit: Option<u32>,
     },
     /// Return the contract's configuration
+    #[returns(ContractInfoResponse)]
     ContractInfo {},
+    /// Return the address allowed to mint new tokens
+    #[returns(MinterResponse)]
+    Minter {},
+    /// Return the contract owner, the pending owner and the transfer deadline
+    #[returns(crate::ownership::Ownership)]
+    Ownership {},
+    /// List the holders of a role
+    #[returns(RoleHoldersResponse)]
+    RoleHolders {
+        role: Role,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List the roles held by an address
+    #[returns(RolesResponse)]
+    Roles { address: String },
+    /// Return the roles allowed to perform each gated action
+    #[returns(RolePolicyResponse)]
+    RolePolicy {},
+    /// Return the categories of messages currently paused
+    #[returns(PauseStatusResponse)]
+    PauseStatus {},
 
     // Original library-specific queries
+    #[returns(Option<Addr>)]
     GetBorrower { token_id: String },
+    #[returns(BookResponse)]
     GetBook { token_id: String },
-    GetAllBooks {},
-    GetBorrowedBooks {},
-    GetMyBorrowedBooks { borrower: Addr },
-    GetAvailableBooks {},
+    /// List books, `next_start_after` in the response fetches the next page
+    #[returns(BooksResponse)]
+    GetAllBooks {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List books on loan together with their borrower
+    #[returns(BooksResponse)]
+    GetBorrowedBooks {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List books on loan to a borrower
+    #[returns(BooksResponse)]
+    GetMyBorrowedBooks {
+        borrower: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List books not on loan
+    #[returns(BooksResponse)]
+    GetAvailableBooks {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Return the current loan of a book, if any
+    #[returns(Option<LoanResponse>)]
+    GetLoan { token_id: String },
+    /// List loans past their due date
+    #[returns(LoansResponse)]
+    GetOverdueLoans {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List the delegates allowed to borrow on behalf of a borrower
+    #[returns(OperatorsResponse)]
+    BorrowDelegates {
+        borrower: String,
+        /// unset or false will filter out expired items, you must set to true to see them
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// List pending borrow requests for books of an owner
+    #[returns(BorrowRequestsResponse)]
+    BorrowRequestsByOwner {
+        owner: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// List pending borrow requests made by a requester
+    #[returns(BorrowRequestsResponse)]
+    BorrowRequestsByRequester {
+        requester: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Return the reservation and the queue of holders of a book
+    #[returns(HoldsResponse)]
+    GetHolds { token_id: String },
+    /// Return the contract-wide lending configuration
+    #[returns(LoanConfig)]
+    LoanConfig {},
+    /// Return the share of borrow fees kept by the contract and the contract-wide late fee
+    #[returns(FeeConfig)]
+    FeeConfig {},
+    /// List the late fees an address left unpaid
+    #[returns(OutstandingFeesResponse)]
+    GetOutstandingFees { address: String },
+    /// List the CW20 contracts accepted as payment
+    #[returns(AcceptedCw20Response)]
+    AcceptedCw20 {},
+    /// Return the shares of a book's borrow fees and tips going to its owner, author and the platform
+    #[returns(RoyaltyInfoResponse)]
+    GetRoyaltyInfo { token_id: String },
+    /// CW2981 royalty queries for marketplaces, answered with the response of the query sent
+    #[returns(Cw2981Response)]
+    Extension { msg: Cw2981QueryMsg },
+    /// Return the platform fees collected and not yet withdrawn, per denom
+    #[returns(TreasuryResponse)]
+    Treasury {},
+    /// Return the total deposits held in escrow, per denom
+    #[returns(EscrowResponse)]
+    Escrow {},
+    /// List the loans holding a deposit
+    #[returns(LoansResponse)]
+    GetDeposits {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 /// Message type for `nft_info` response
@@ -152,4 +458,129 @@ pub struct BookInfoResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MinterResponse {
     pub minter: String,
-}
\ No newline at end of file
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoleHoldersResponse {
+    pub holders: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionPolicy {
+    pub action: String,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolePolicyResponse {
+    pub policies: Vec<ActionPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseStatusResponse {
+    pub paused: Vec<PauseCategory>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LoanResponse {
+    pub token_id: String,
+    pub borrower: Addr,
+    pub start_time: Timestamp,
+    pub start_height: u64,
+    pub due: Expiration,
+    pub renewals: u32,
+    pub deposit: Option<Coin>,
+    /// Late fee accrued so far, if the book were returned at the current block
+    pub late_fee: Option<Coin>,
+    /// Whether the due date has passed at the current block
+    pub overdue: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LoansResponse {
+    pub loans: Vec<LoanResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HoldsResponse {
+    /// Holder the book is currently set aside for
+    pub reservation: Option<Reservation>,
+    /// Members waiting in line, first in first out
+    pub holds: Vec<Hold>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BorrowRequestResponse {
+    pub request_id: u64,
+    pub token_id: String,
+    pub requester: Addr,
+    pub owner: Addr,
+    pub created_at: Timestamp,
+    pub payment: Option<Coin>,
+    pub deposit: Option<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BorrowRequestsResponse {
+    pub requests: Vec<BorrowRequestResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowResponse {
+    pub deposits: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutstandingFeeResponse {
+    pub token_id: String,
+    pub owner: Addr,
+    pub amount: Coin,
+    pub incurred_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutstandingFeesResponse {
+    pub fees: Vec<OutstandingFeeResponse>,
+    /// Sum of the unpaid fees, per denom
+    pub total: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AcceptedCw20Response {
+    pub tokens: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TreasuryResponse {
+    pub balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoyaltyInfoResponse {
+    pub token_id: String,
+    pub owner: Addr,
+    /// Author payout address, if the book shares its revenue with its author
+    pub author: Option<Addr>,
+    pub owner_bps: u16,
+    pub author_bps: u16,
+    pub platform_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BookResponse {
+    pub token_id: String,
+    pub book: Book,
+    /// Current borrower, if the book is on loan
+    pub borrower: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BooksResponse {
+    pub books: Vec<BookResponse>,
+    /// Pass as `start_after` to get the next page, unset on the last page
+    pub next_start_after: Option<String>,
+}
@@ -0,0 +1,2090 @@
+use cosmwasm_std::{
+    coins, from_json, to_json_binary, to_json_vec, Addr, Binary, Coin, Deps, DepsMut, Empty, Env, Event, MessageInfo, Response,
+    OwnedDeps, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
+use cw_storage_plus::{Item, Map};
+use cw_utils::{Duration, Expiration};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::cw20::{cw20_denom, Cw20ReceiveMsg};
+use crate::cw2981::{Cw2981QueryMsg, RoyaltiesInfoResponse};
+use crate::error::ContractError;
+use crate::msg::{
+    BookResponse, BooksResponse, BorrowRequestsResponse, EscrowResponse, ExecuteMsg, HoldsResponse, InstantiateMsg, LoanResponse, LoansResponse, MigrateMsg, OutstandingFeesResponse, QueryMsg,
+    ReceiveMsg, RevenueSplitMsg, RoyaltyInfoResponse, TreasuryResponse,
+};
+use crate::state::{CONTRACT_INFO, LateFee, LendingPolicy, Metadata, PauseCategory, Reservation, Role, MAX_HOLDS};
+
+const OWNER: &str = "owner";
+const MINTER: &str = "minter";
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+const DENOM: &str = "uatom";
+const DAY: u64 = 24 * 60 * 60;
+
+fn library_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(
+        ContractWrapper::new(crate::execute, crate::instantiate, crate::query)
+            .with_migrate(crate::migrate),
+    )
+}
+
+// A contract accepting CW721 sends, it keeps the last `receive_nft` message it got.
+// Unknown fields are refused so the test sees exactly what the library sends.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ReceiverExecuteMsg {
+    ReceiveNft(ReceivedNft),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ReceivedNft {
+    sender: String,
+    token_id: String,
+    msg: Binary,
+}
+
+const RECEIVED: Item<ReceivedNft> = Item::new("received");
+
+fn receiver_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        |deps: DepsMut, _env: Env, _info: MessageInfo, msg: ReceiverExecuteMsg| -> StdResult<Response> {
+            let ReceiverExecuteMsg::ReceiveNft(received) = msg;
+            RECEIVED.save(deps.storage, &received)?;
+            Ok(Response::new())
+        },
+        |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+            Ok(Response::new())
+        },
+        |deps: Deps, _env: Env, _msg: Empty| -> StdResult<Binary> {
+            to_json_binary(&RECEIVED.load(deps.storage)?)
+        },
+    ))
+}
+
+// A CW20 token with just what the library uses: `send`, `transfer` and `balance`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum TokenExecuteMsg {
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum TokenQueryMsg {
+    Balance { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct TokenBalanceResponse {
+    balance: Uint128,
+}
+
+const TOKEN_BALANCES: Map<&str, Uint128> = Map::new("balances");
+
+fn token_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        |deps: DepsMut, _env: Env, info: MessageInfo, msg: TokenExecuteMsg| -> StdResult<Response> {
+            let (recipient, amount) = match &msg {
+                TokenExecuteMsg::Send { contract, amount, .. } => (contract, *amount),
+                TokenExecuteMsg::Transfer { recipient, amount } => (recipient, *amount),
+            };
+            TOKEN_BALANCES.update(deps.storage, info.sender.as_str(), |balance| {
+                Ok::<_, StdError>(balance.unwrap_or_default().checked_sub(amount)?)
+            })?;
+            TOKEN_BALANCES.update(deps.storage, recipient, |balance| {
+                Ok::<_, StdError>(balance.unwrap_or_default() + amount)
+            })?;
+            let mut response = Response::new();
+            if let TokenExecuteMsg::Send { contract, msg, .. } = msg {
+                response = response.add_message(WasmMsg::Execute {
+                    contract_addr: contract,
+                    msg: to_json_binary(&ExecuteMsg::Receive(Cw20ReceiveMsg {
+                        sender: info.sender.to_string(),
+                        amount,
+                        msg,
+                    }))?,
+                    funds: vec![],
+                });
+            }
+            Ok(response)
+        },
+        |deps: DepsMut, _env: Env, _info: MessageInfo, balances: Vec<(String, Uint128)>| -> StdResult<Response> {
+            for (address, balance) in balances {
+                TOKEN_BALANCES.save(deps.storage, &address, &balance)?;
+            }
+            Ok(Response::new())
+        },
+        |deps: Deps, _env: Env, msg: TokenQueryMsg| -> StdResult<Binary> {
+            let TokenQueryMsg::Balance { address } = msg;
+            let balance = TOKEN_BALANCES.may_load(deps.storage, &address)?.unwrap_or_default();
+            to_json_binary(&TokenBalanceResponse { balance })
+        },
+    ))
+}
+
+fn instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        name: "Library".to_string(),
+        symbol: "BOOK".to_string(),
+        minter: MINTER.to_string(),
+        owner: Some(OWNER.to_string()),
+        default_loan_period: None,
+        max_renewals: None,
+        pickup_window: None,
+        platform_fee_bps: None,
+        late_fee: None,
+        deposit_grace_period: None,
+        transfer_loans: None,
+    }
+}
+
+struct Suite {
+    app: App,
+    library: Addr,
+    code_id: u64,
+}
+
+impl Suite {
+    fn new() -> Self {
+        Self::with(instantiate_msg())
+    }
+
+    fn with(msg: InstantiateMsg) -> Self {
+        let mut app = AppBuilder::new().build(|router, _api, storage| {
+            for member in [ALICE, BOB] {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked(member), coins(10_000, DENOM))
+                    .unwrap();
+            }
+        });
+        let code_id = app.store_code(library_contract());
+        let library = app
+            .instantiate_contract(code_id, Addr::unchecked(OWNER), &msg, &[], "library", Some(OWNER.to_string()))
+            .unwrap();
+        Suite { app, library, code_id }
+    }
+
+    fn execute(
+        &mut self,
+        sender: &str,
+        msg: ExecuteMsg,
+        funds: &[Coin],
+    ) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(Addr::unchecked(sender), self.library.clone(), &msg, funds)
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn query<T: DeserializeOwned>(&self, msg: QueryMsg) -> T {
+        self.app.wrap().query_wasm_smart(&self.library, &msg).unwrap()
+    }
+
+    fn balance(&self, address: &str) -> u128 {
+        self.app.wrap().query_balance(address, DENOM).unwrap().amount.u128()
+    }
+
+    fn instantiate_token(&mut self) -> Addr {
+        let code_id = self.app.store_code(token_contract());
+        let balances = vec![(BOB.to_string(), Uint128::new(10_000))];
+        self.app
+            .instantiate_contract(code_id, Addr::unchecked(OWNER), &balances, &[], "token", None)
+            .unwrap()
+    }
+
+    fn token_balance(&self, token: &Addr, address: &str) -> u128 {
+        let response: TokenBalanceResponse = self
+            .app
+            .wrap()
+            .query_wasm_smart(
+                token,
+                &TokenQueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )
+            .unwrap();
+        response.balance.u128()
+    }
+
+    fn send_tokens(
+        &mut self,
+        token: &Addr,
+        sender: &str,
+        amount: u128,
+        msg: ReceiveMsg,
+    ) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(
+                Addr::unchecked(sender),
+                token.clone(),
+                &TokenExecuteMsg::Send {
+                    contract: self.library.to_string(),
+                    amount: Uint128::new(amount),
+                    msg: to_json_binary(&msg).unwrap(),
+                },
+                &[],
+            )
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn add_book(&mut self, sender: &str, token_id: &str) -> Result<AppResponse, ContractError> {
+        self.execute(
+            sender,
+            ExecuteMsg::AddBook {
+                token_id: token_id.to_string(),
+                title: format!("Book {}", token_id),
+                author: "Author".to_string(),
+                url: format!("https://example.com/{}.txt", token_id),
+                owner: Addr::unchecked(ALICE),
+                co_authors: vec![],
+                isbn: None,
+                publication_year: None,
+                genres: vec![],
+                publisher: None,
+                language: None,
+                page_count: None,
+                description: None,
+                image: None,
+            },
+            &[],
+        )
+    }
+
+    fn mint(&mut self, token_id: &str, owner: &str) {
+        let metadata = Metadata {
+            title: format!("Book {}", token_id),
+            author: "Author".to_string(),
+            url: format!("https://example.com/{}.txt", token_id),
+            ..Default::default()
+        };
+        self.execute(
+            MINTER,
+            ExecuteMsg::Mint {
+                token_id: token_id.to_string(),
+                owner: owner.to_string(),
+                metadata,
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn transfer(&mut self, sender: &str, token_id: &str, recipient: &str) -> Result<AppResponse, ContractError> {
+        self.execute(
+            sender,
+            ExecuteMsg::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: token_id.to_string(),
+            },
+            &[],
+        )
+    }
+
+    fn burn(&mut self, sender: &str, token_id: &str) -> Result<AppResponse, ContractError> {
+        self.execute(
+            sender,
+            ExecuteMsg::Burn {
+                token_id: token_id.to_string(),
+            },
+            &[],
+        )
+    }
+
+    fn set_price(&mut self, owner: &str, token_id: &str, amount: u128) {
+        self.execute(
+            owner,
+            ExecuteMsg::SetBorrowPrice {
+                token_id: token_id.to_string(),
+                price: Some(Coin::new(amount, DENOM)),
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn set_policy(&mut self, owner: &str, token_id: &str, policy: LendingPolicy) {
+        self.execute(
+            owner,
+            ExecuteMsg::SetLendingPolicy {
+                token_id: token_id.to_string(),
+                policy,
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn set_deposit(&mut self, owner: &str, token_id: &str, amount: u128) {
+        self.execute(
+            owner,
+            ExecuteMsg::SetDeposit {
+                token_id: token_id.to_string(),
+                deposit: Some(Coin::new(amount, DENOM)),
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn set_late_fee(&mut self, owner: &str, token_id: &str, amount: u128, per: u64) {
+        self.execute(
+            owner,
+            ExecuteMsg::SetLateFee {
+                token_id: token_id.to_string(),
+                late_fee: Some(LateFee {
+                    amount: Coin::new(amount, DENOM),
+                    per: Duration::Time(per),
+                }),
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn advance_days(&mut self, days: u64) {
+        self.app.update_block(|block| {
+            block.time = block.time.plus_seconds(days * DAY);
+            block.height += days * 24 * 60 * 12;
+        });
+    }
+
+    fn treasury(&self) -> Vec<Coin> {
+        self.query::<TreasuryResponse>(QueryMsg::Treasury {}).balances
+    }
+
+    fn withdraw(&mut self, sender: &str, amount: u128) -> Result<AppResponse, ContractError> {
+        self.execute(
+            sender,
+            ExecuteMsg::WithdrawTreasury {
+                amount: Coin::new(amount, DENOM),
+                recipient: "treasurer".to_string(),
+            },
+            &[],
+        )
+    }
+
+    fn escrowed(&self) -> Vec<Coin> {
+        self.query::<EscrowResponse>(QueryMsg::Escrow {}).deposits
+    }
+
+    fn return_book(&mut self, borrower: &str, token_id: &str, funds: &[Coin]) -> Result<AppResponse, ContractError> {
+        self.execute(
+            borrower,
+            ExecuteMsg::ReturnBook {
+                token_id: token_id.to_string(),
+            },
+            funds,
+        )
+    }
+
+    fn loan(&self, token_id: &str) -> Option<LoanResponse> {
+        self.query(QueryMsg::GetLoan {
+            token_id: token_id.to_string(),
+        })
+    }
+
+    fn renew(&mut self, borrower: &str, token_id: &str) -> Result<AppResponse, ContractError> {
+        self.execute(
+            borrower,
+            ExecuteMsg::RenewLoan {
+                token_id: token_id.to_string(),
+            },
+            &[],
+        )
+    }
+
+    fn place_hold(&mut self, holder: &str, token_id: &str) -> Result<AppResponse, ContractError> {
+        self.execute(
+            holder,
+            ExecuteMsg::PlaceHold {
+                token_id: token_id.to_string(),
+            },
+            &[],
+        )
+    }
+
+    fn holds(&self, token_id: &str) -> HoldsResponse {
+        self.query(QueryMsg::GetHolds {
+            token_id: token_id.to_string(),
+        })
+    }
+
+    fn borrow(&mut self, borrower: &str, token_id: &str, funds: &[Coin]) -> Result<AppResponse, ContractError> {
+        self.execute(
+            borrower,
+            ExecuteMsg::BorrowBook {
+                token_id: token_id.to_string(),
+            },
+            funds,
+        )
+    }
+}
+
+#[test]
+fn send_nft_calls_receive_nft_on_the_contract() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let code_id = suite.app.store_code(receiver_contract());
+    let receiver = suite
+        .app
+        .instantiate_contract(code_id, Addr::unchecked(OWNER), &Empty {}, &[], "receiver", None)
+        .unwrap();
+
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SendNft {
+                contract: receiver.to_string(),
+                token_id: "1".to_string(),
+                msg: Binary::from(b"hello".as_slice()),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let received: ReceivedNft = suite.app.wrap().query_wasm_smart(&receiver, &Empty {}).unwrap();
+    assert_eq!(
+        received,
+        ReceivedNft {
+            sender: ALICE.to_string(),
+            token_id: "1".to_string(),
+            msg: Binary::from(b"hello".as_slice()),
+        }
+    );
+    let owner: cw721::OwnerOfResponse = suite.query(QueryMsg::OwnerOf {
+        token_id: "1".to_string(),
+        include_expired: None,
+    });
+    assert_eq!(owner.owner, receiver.to_string());
+}
+
+#[test]
+fn send_nft_refuses_books_on_loan() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    let err = suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SendNft {
+                contract: "receiver".to_string(),
+                token_id: "1".to_string(),
+                msg: Binary::default(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::BookOnLoan {}), "{:?}", err);
+}
+
+#[test]
+fn borrow_fee_goes_to_the_owner() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+
+    assert_eq!(suite.balance(ALICE), 10_100);
+    assert_eq!(suite.balance(BOB), 9_900);
+    assert_eq!(suite.balance(suite.library.as_str()), 0);
+}
+
+#[test]
+fn borrow_fee_must_be_attached_exactly() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+
+    let err = suite.borrow(BOB, "1", &coins(50, DENOM)).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFunds { .. }), "{:?}", err);
+    let err = suite.borrow(BOB, "1", &coins(150, DENOM)).unwrap_err();
+    assert!(matches!(err, ContractError::ExcessFunds { .. }), "{:?}", err);
+    let err = suite.borrow(BOB, "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFunds { .. }), "{:?}", err);
+
+    // Owners take their own books for free
+    suite.borrow(ALICE, "1", &[]).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_000);
+}
+
+#[test]
+fn deposit_is_escrowed_during_the_loan_and_refunded_on_return() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite.set_deposit(ALICE, "1", 500);
+
+    suite.borrow(BOB, "1", &coins(600, DENOM)).unwrap();
+    assert_eq!(suite.balance(BOB), 9_400);
+    assert_eq!(suite.balance(suite.library.as_str()), 500);
+    assert_eq!(suite.escrowed(), coins(500, DENOM));
+
+    suite.return_book(BOB, "1", &[]).unwrap();
+    assert_eq!(suite.balance(BOB), 9_900);
+    assert_eq!(suite.balance(ALICE), 10_100);
+    assert_eq!(suite.balance(suite.library.as_str()), 0);
+    assert_eq!(suite.escrowed(), vec![]);
+}
+
+#[test]
+fn rejected_and_cancelled_requests_are_refunded() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite.set_deposit(ALICE, "1", 500);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetLendingPolicy {
+                token_id: "1".to_string(),
+                policy: LendingPolicy::ApprovalRequired,
+            },
+            &[],
+        )
+        .unwrap();
+
+    suite.borrow(BOB, "1", &coins(600, DENOM)).unwrap();
+    assert_eq!(suite.balance(suite.library.as_str()), 600);
+    suite
+        .execute(ALICE, ExecuteMsg::RejectBorrowRequest { request_id: 1 }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(BOB), 10_000);
+    assert_eq!(suite.balance(suite.library.as_str()), 0);
+    assert_eq!(suite.escrowed(), vec![]);
+
+    suite.borrow(BOB, "1", &coins(600, DENOM)).unwrap();
+    suite
+        .execute(BOB, ExecuteMsg::CancelBorrowRequest { request_id: 2 }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(BOB), 10_000);
+    assert_eq!(suite.escrowed(), vec![]);
+}
+
+#[test]
+fn owner_claims_the_deposit_after_the_grace_period() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_deposit(ALICE, "1", 500);
+    suite.borrow(BOB, "1", &coins(500, DENOM)).unwrap();
+
+    // Due after two weeks, claimable one more week later
+    suite.advance_days(20);
+    let claim = ExecuteMsg::ClaimDeposit {
+        token_id: "1".to_string(),
+    };
+    let err = suite.execute(ALICE, claim.clone(), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::DepositNotClaimable {}), "{:?}", err);
+    let err = suite.execute(BOB, claim.clone(), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+
+    suite.advance_days(2);
+    suite.execute(ALICE, claim, &[]).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_500);
+    assert_eq!(suite.balance(BOB), 9_500);
+    assert_eq!(suite.escrowed(), vec![]);
+    let loan: Option<LoanResponse> = suite.query(QueryMsg::GetLoan {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(loan, None);
+}
+
+#[test]
+fn grace_period_must_count_the_unit_of_the_loan_period() {
+    let mut app = App::default();
+    let code_id = app.store_code(library_contract());
+    let msg = InstantiateMsg {
+        default_loan_period: Some(Duration::Height(100_000)),
+        ..instantiate_msg()
+    };
+    let err = app
+        .instantiate_contract(code_id, Addr::unchecked(OWNER), &msg, &[], "library", None)
+        .unwrap_err();
+    assert!(matches!(err.downcast().unwrap(), ContractError::MismatchedDurations {}));
+
+    let mut suite = Suite::new();
+    let err = suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateLoanConfig {
+                default_loan_period: None,
+                max_renewals: None,
+                pickup_window: None,
+                deposit_grace_period: Some(Duration::Height(1_000)),
+                transfer_loans: None,
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::MismatchedDurations {}), "{:?}", err);
+
+    suite.mint("1", ALICE);
+    let err = suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetLoanPeriod {
+                token_id: "1".to_string(),
+                loan_period: Some(Duration::Height(1_000)),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::MismatchedDurations {}), "{:?}", err);
+}
+
+#[test]
+fn late_fee_is_taken_from_the_deposit_then_the_funds() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_deposit(ALICE, "1", 20);
+    suite.set_late_fee(ALICE, "1", 10, DAY);
+    suite.borrow(BOB, "1", &coins(20, DENOM)).unwrap();
+
+    // Three days late
+    suite.advance_days(17);
+    let loan: Option<LoanResponse> = suite.query(QueryMsg::GetLoan {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(loan.unwrap().late_fee, Some(Coin::new(30, DENOM)));
+
+    suite.return_book(BOB, "1", &coins(10, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_030);
+    assert_eq!(suite.balance(BOB), 9_970);
+    assert_eq!(suite.balance(suite.library.as_str()), 0);
+    assert_eq!(suite.escrowed(), vec![]);
+}
+
+#[test]
+fn unpaid_late_fees_block_borrows_until_paid() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_late_fee(ALICE, "1", 10, DAY);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(17);
+    suite.return_book(BOB, "1", &[]).unwrap();
+
+    let fees: OutstandingFeesResponse = suite.query(QueryMsg::GetOutstandingFees {
+        address: BOB.to_string(),
+    });
+    assert_eq!(fees.total, coins(30, DENOM));
+    let err = suite.borrow(BOB, "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::OutstandingFees {}), "{:?}", err);
+
+    suite.execute(BOB, ExecuteMsg::PayFees {}, &coins(30, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_030);
+    suite.borrow(BOB, "1", &[]).unwrap();
+}
+
+#[test]
+fn loans_keep_the_late_fee_in_force_when_borrowed() {
+    let mut suite = Suite::new();
+    let late_fee = |amount| LateFee {
+        amount: Coin::new(amount, DENOM),
+        per: Duration::Time(DAY),
+    };
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateFeeConfig {
+                platform_fee_bps: 0,
+                late_fee: Some(late_fee(10)),
+            },
+            &[],
+        )
+        .unwrap();
+    suite.mint("1", ALICE);
+    suite.set_deposit(ALICE, "1", 500);
+    suite.borrow(BOB, "1", &coins(500, DENOM)).unwrap();
+
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateFeeConfig {
+                platform_fee_bps: 0,
+                late_fee: Some(late_fee(100)),
+            },
+            &[],
+        )
+        .unwrap();
+    suite.set_late_fee(ALICE, "1", 200, DAY);
+
+    suite.advance_days(17);
+    suite.return_book(BOB, "1", &[]).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_030);
+    assert_eq!(suite.balance(BOB), 9_970);
+}
+
+#[test]
+fn late_fee_must_count_the_unit_of_the_loan_period() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let late_fee = Some(LateFee {
+        amount: Coin::new(10, DENOM),
+        per: Duration::Height(100),
+    });
+
+    let err = suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetLateFee {
+                token_id: "1".to_string(),
+                late_fee: late_fee.clone(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::MismatchedDurations {}), "{:?}", err);
+    let err = suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateFeeConfig {
+                platform_fee_bps: 0,
+                late_fee,
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::MismatchedDurations {}), "{:?}", err);
+}
+
+#[test]
+fn late_fee_periods_longer_than_the_delay_are_charged_once() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_late_fee(ALICE, "1", 10, u64::MAX);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(15);
+
+    suite.return_book(BOB, "1", &coins(10, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_010);
+}
+
+#[test]
+fn overflowing_late_fee_saturates() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_late_fee(ALICE, "1", u128::MAX, 1);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(15);
+
+    let loan: Option<LoanResponse> = suite.query(QueryMsg::GetLoan {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(loan.unwrap().late_fee, Some(Coin::new(u128::MAX, DENOM)));
+    suite.return_book(BOB, "1", &[]).unwrap();
+    let fees: OutstandingFeesResponse = suite.query(QueryMsg::GetOutstandingFees {
+        address: BOB.to_string(),
+    });
+    assert_eq!(fees.total, coins(u128::MAX, DENOM));
+    suite.borrow(ALICE, "1", &[]).unwrap();
+}
+
+#[test]
+fn accepted_cw20_pays_borrow_fees_and_deposits() {
+    let mut suite = Suite::new();
+    let token = suite.instantiate_token();
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateAcceptedCw20 {
+                add: vec![token.to_string()],
+                remove: vec![],
+            },
+            &[],
+        )
+        .unwrap();
+    suite.mint("1", ALICE);
+    let denom = cw20_denom(&token);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetBorrowPrice {
+                token_id: "1".to_string(),
+                price: Some(Coin::new(100, &denom)),
+            },
+            &[],
+        )
+        .unwrap();
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetDeposit {
+                token_id: "1".to_string(),
+                deposit: Some(Coin::new(500, &denom)),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let borrow = ReceiveMsg::BorrowBook {
+        token_id: "1".to_string(),
+    };
+    let err = suite.send_tokens(&token, BOB, 500, borrow.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFunds { .. }), "{:?}", err);
+    suite.send_tokens(&token, BOB, 600, borrow).unwrap();
+    assert_eq!(suite.token_balance(&token, ALICE), 100);
+    assert_eq!(suite.token_balance(&token, suite.library.as_str()), 500);
+    assert_eq!(suite.escrowed(), vec![Coin::new(500, &denom)]);
+
+    suite.return_book(BOB, "1", &[]).unwrap();
+    assert_eq!(suite.token_balance(&token, BOB), 9_900);
+    assert_eq!(suite.token_balance(&token, suite.library.as_str()), 0);
+    assert_eq!(suite.escrowed(), vec![]);
+}
+
+#[test]
+fn cw20_not_accepted_is_refused() {
+    let mut suite = Suite::new();
+    let token = suite.instantiate_token();
+    suite.mint("1", ALICE);
+    let borrow = ReceiveMsg::BorrowBook {
+        token_id: "1".to_string(),
+    };
+
+    let err = suite.send_tokens(&token, BOB, 100, borrow.clone()).unwrap_err();
+    assert!(
+        matches!(&err, ContractError::Cw20NotAccepted { address } if *address == token),
+        "{:?}",
+        err
+    );
+
+    // Tokens that are no longer accepted are refused too
+    let update = |add: Vec<String>, remove: Vec<String>| ExecuteMsg::UpdateAcceptedCw20 { add, remove };
+    suite.execute(OWNER, update(vec![token.to_string()], vec![]), &[]).unwrap();
+    suite.execute(OWNER, update(vec![], vec![token.to_string()]), &[]).unwrap();
+    let err = suite.send_tokens(&token, BOB, 100, borrow).unwrap_err();
+    assert!(matches!(err, ContractError::Cw20NotAccepted { .. }), "{:?}", err);
+    assert_eq!(suite.token_balance(&token, BOB), 10_000);
+}
+
+#[test]
+fn empty_role_policy_opens_the_action_to_everyone() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let policy = |roles| ExecuteMsg::SetRolePolicy {
+        action: "borrow_book".to_string(),
+        roles,
+    };
+    suite.execute(OWNER, policy(vec![Role::Member]), &[]).unwrap();
+    let err = suite.borrow(BOB, "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+
+    let response = suite.execute(OWNER, policy(vec![]), &[]).unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("roles", "everyone")));
+    suite.borrow(BOB, "1", &[]).unwrap();
+}
+
+#[test]
+fn empty_staff_policy_falls_back_to_the_minter_and_the_borrower() {
+    let mut suite = Suite::new();
+    let policy = |action: &str| ExecuteMsg::SetRolePolicy {
+        action: action.to_string(),
+        roles: vec![],
+    };
+    let response = suite.execute(OWNER, policy("mint"), &[]).unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("roles", "minter")));
+    let response = suite.execute(OWNER, policy("force_return"), &[]).unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("roles", "borrower")));
+
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::GrantRole {
+                role: Role::Librarian,
+                address: "librarian".to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+    let err = suite
+        .execute(
+            "librarian",
+            ExecuteMsg::Mint {
+                token_id: "1".to_string(),
+                owner: ALICE.to_string(),
+                metadata: Metadata::default(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    let err = suite.return_book("librarian", "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+}
+
+#[test]
+fn approvals_can_be_revoked_while_transfers_are_paused() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::Approve {
+                spender: BOB.to_string(),
+                token_id: "1".to_string(),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::Pause {
+                categories: Some(vec![PauseCategory::Transfers]),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let err = suite
+        .execute(
+            BOB,
+            ExecuteMsg::TransferNft {
+                recipient: BOB.to_string(),
+                token_id: "1".to_string(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Paused { .. }), "{:?}", err);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::Revoke {
+                spender: BOB.to_string(),
+                token_id: "1".to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::RevokeAll {
+                operator: BOB.to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+
+    suite.execute(OWNER, ExecuteMsg::Unpause { categories: None }, &[]).unwrap();
+}
+
+#[test]
+fn migrating_to_the_same_version_applies_no_step() {
+    let mut suite = Suite::new();
+    let response = suite
+        .app
+        .migrate_contract(
+            Addr::unchecked(OWNER),
+            suite.library.clone(),
+            &MigrateMsg {
+                owner: None,
+                minter: None,
+                from_version: None,
+            },
+            suite.code_id,
+        )
+        .unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("steps", "none")));
+}
+
+// Storage layout of 0.1.0, the only released version. Its contract info was saved over the cw2 version
+#[derive(Serialize, Deserialize)]
+struct BaselineMetadata {
+    title: String,
+    author: String,
+    url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BaselineToken {
+    owner: Addr,
+    approvals: Vec<Empty>,
+    metadata: BaselineMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BaselineBook {
+    title: String,
+    author: String,
+    url: String,
+    owner: Addr,
+}
+
+const BASELINE_TOKENS: Map<&str, BaselineToken> = Map::new("tokens");
+const BASELINE_BOOKS: Map<&str, BaselineBook> = Map::new("books");
+const BASELINE_BORROWERS: Map<&str, Addr> = Map::new("borrowers");
+const BASELINE_NUM_TOKENS: Item<u64> = Item::new("num_tokens");
+
+fn baseline_deps() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+    let mut deps = mock_dependencies();
+    let contract_info = cw721::ContractInfoResponse {
+        name: "Library".to_string(),
+        symbol: "BOOK".to_string(),
+    };
+    deps.storage.set(b"contract_info", &to_json_vec(&contract_info).unwrap());
+    deps
+}
+
+// AddBook wrote both TOKENS and BOOKS, Mint only TOKENS
+fn baseline_token(storage: &mut dyn Storage, token_id: &str, owner: &str) {
+    let token = BaselineToken {
+        owner: Addr::unchecked(owner),
+        approvals: vec![],
+        metadata: BaselineMetadata {
+            title: format!("Book {}", token_id),
+            author: "Author".to_string(),
+            url: format!("https://example.com/{}.txt", token_id),
+        },
+    };
+    BASELINE_TOKENS.save(storage, token_id, &token).unwrap();
+}
+
+fn baseline_book(storage: &mut dyn Storage, token_id: &str, owner: &str) {
+    let book = BaselineBook {
+        title: format!("Book {}", token_id),
+        author: "Author".to_string(),
+        url: format!("https://example.com/{}.txt", token_id),
+        owner: Addr::unchecked(owner),
+    };
+    BASELINE_BOOKS.save(storage, token_id, &book).unwrap();
+}
+
+fn migrate_from_baseline(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+    let msg = MigrateMsg {
+        owner: Some(OWNER.to_string()),
+        minter: Some(MINTER.to_string()),
+        from_version: Some("0.1.0".to_string()),
+    };
+    crate::migrate(deps.as_mut(), mock_env(), msg).unwrap();
+}
+
+fn query_deps<T: DeserializeOwned>(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, msg: QueryMsg) -> T {
+    from_json(crate::query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+}
+
+#[test]
+fn migrating_from_0_1_0_keeps_books_on_loan() {
+    let mut deps = baseline_deps();
+    baseline_token(&mut deps.storage, "1", ALICE);
+    baseline_book(&mut deps.storage, "1", ALICE);
+    baseline_token(&mut deps.storage, "2", ALICE);
+    BASELINE_BORROWERS.save(&mut deps.storage, "1", &Addr::unchecked(BOB)).unwrap();
+    BASELINE_NUM_TOKENS.save(&mut deps.storage, &2).unwrap();
+
+    migrate_from_baseline(&mut deps);
+
+    let loan: Option<LoanResponse> = query_deps(
+        &deps,
+        QueryMsg::GetLoan {
+            token_id: "1".to_string(),
+        },
+    );
+    assert_eq!(loan.unwrap().borrower, BOB);
+    let borrowed: BooksResponse = query_deps(
+        &deps,
+        QueryMsg::GetMyBorrowedBooks {
+            borrower: Addr::unchecked(BOB),
+            start_after: None,
+            limit: None,
+        },
+    );
+    let token_ids: Vec<_> = borrowed.books.iter().map(|book| book.token_id.as_str()).collect();
+    assert_eq!(token_ids, ["1"]);
+}
+
+#[test]
+fn migrating_from_0_1_0_leaves_burned_books_out() {
+    let mut deps = baseline_deps();
+    // Added, minted, then added and burned
+    baseline_token(&mut deps.storage, "1", ALICE);
+    baseline_book(&mut deps.storage, "1", ALICE);
+    baseline_token(&mut deps.storage, "2", ALICE);
+    baseline_book(&mut deps.storage, "3", ALICE);
+    BASELINE_NUM_TOKENS.save(&mut deps.storage, &2).unwrap();
+
+    migrate_from_baseline(&mut deps);
+
+    let count: cw721::NumTokensResponse = query_deps(&deps, QueryMsg::NumTokens {});
+    assert_eq!(count.count, 2);
+    let err = crate::query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetBook {
+            token_id: "3".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, StdError::NotFound { .. }), "{:?}", err);
+    let all: BooksResponse = query_deps(
+        &deps,
+        QueryMsg::GetAllBooks {
+            start_after: None,
+            limit: None,
+        },
+    );
+    let token_ids: Vec<_> = all.books.iter().map(|book| book.token_id.as_str()).collect();
+    assert_eq!(token_ids, ["1", "2"]);
+}
+
+#[test]
+fn migrate_recovers_the_version_overwritten_by_the_contract_info() {
+    let mut deps = mock_dependencies();
+    crate::instantiate(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), instantiate_msg()).unwrap();
+    // Lay the contract info out as in 0.1.0, over the cw2 version
+    let contract_info = CONTRACT_INFO.load(&deps.storage).unwrap();
+    CONTRACT_INFO.remove(&mut deps.storage);
+    deps.storage.set(b"contract_info", &to_json_vec(&contract_info).unwrap());
+
+    let msg = |from_version: Option<&str>| MigrateMsg {
+        owner: None,
+        minter: None,
+        from_version: from_version.map(str::to_string),
+    };
+    let err = crate::migrate(deps.as_mut(), mock_env(), msg(None)).unwrap_err();
+    assert!(
+        matches!(&err, ContractError::MissingMigrateField { field } if field == "from_version"),
+        "{:?}",
+        err
+    );
+
+    crate::migrate(deps.as_mut(), mock_env(), msg(Some("0.6.0"))).unwrap();
+    assert_eq!(CONTRACT_INFO.load(&deps.storage).unwrap(), contract_info);
+    let version = cw2::get_contract_version(&deps.storage).unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn platform_fee_goes_to_the_treasury_and_admins_withdraw_it() {
+    let mut suite = Suite::with(InstantiateMsg {
+        platform_fee_bps: Some(1_000),
+        ..instantiate_msg()
+    });
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_090);
+    assert_eq!(suite.treasury(), coins(10, DENOM));
+
+    let err = suite.withdraw(BOB, 10).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    let err = suite.withdraw(OWNER, 11).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientTreasury { .. }), "{:?}", err);
+
+    suite.withdraw(OWNER, 10).unwrap();
+    assert_eq!(suite.balance("treasurer"), 10);
+    assert_eq!(suite.balance(suite.library.as_str()), 0);
+    assert_eq!(suite.treasury(), vec![]);
+}
+
+#[test]
+fn treasury_withdrawals_leave_what_is_held_for_borrowers() {
+    let mut suite = Suite::with(InstantiateMsg {
+        platform_fee_bps: Some(1_000),
+        ..instantiate_msg()
+    });
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite.mint("2", ALICE);
+    suite.set_price(ALICE, "2", 100);
+    suite.set_deposit(ALICE, "2", 50);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetLendingPolicy {
+                token_id: "2".to_string(),
+                policy: LendingPolicy::ApprovalRequired,
+            },
+            &[],
+        )
+        .unwrap();
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+    suite.borrow(BOB, "2", &coins(150, DENOM)).unwrap();
+    assert_eq!(suite.balance(suite.library.as_str()), 160);
+
+    // Without the borrow fee and deposit of the pending request the treasury isn't covered
+    let library = suite.library.clone();
+    let set_library_balance = |suite: &mut Suite, amount: u128| {
+        suite.app.init_modules(|router, _api, storage| {
+            router
+                .bank
+                .init_balance(storage, &library, coins(amount, DENOM))
+                .unwrap();
+        });
+    };
+    set_library_balance(&mut suite, 150);
+    let err = suite.withdraw(OWNER, 10).unwrap_err();
+    assert!(matches!(err, ContractError::TreasuryImbalance { .. }), "{:?}", err);
+
+    set_library_balance(&mut suite, 160);
+    suite.withdraw(OWNER, 10).unwrap();
+    assert_eq!(suite.balance(suite.library.as_str()), 150);
+
+    // Once accepted the borrow fee is paid out, the deposit stays in escrow
+    suite
+        .execute(ALICE, ExecuteMsg::AcceptBorrowRequest { request_id: 1 }, &[])
+        .unwrap();
+    assert_eq!(suite.treasury(), coins(10, DENOM));
+    assert_eq!(suite.escrowed(), coins(50, DENOM));
+    suite.withdraw(OWNER, 10).unwrap();
+    assert_eq!(suite.balance(suite.library.as_str()), 50);
+}
+
+#[test]
+fn revenue_split_shares_borrow_fees_and_tips_with_the_author() {
+    let mut suite = Suite::with(InstantiateMsg {
+        platform_fee_bps: Some(1_000),
+        ..instantiate_msg()
+    });
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    let split = |platform_bps| ExecuteMsg::SetRevenueSplit {
+        token_id: "1".to_string(),
+        split: Some(RevenueSplitMsg {
+            author: "writer".to_string(),
+            owner_bps: 9_000 - platform_bps,
+            author_bps: 1_000,
+            platform_bps,
+        }),
+    };
+    let err = suite.execute(ALICE, split(500), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::PlatformShareTooLow { min: 1_000 }), "{:?}", err);
+    suite.execute(ALICE, split(2_000), &[]).unwrap();
+
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_070);
+    assert_eq!(suite.balance("writer"), 10);
+    assert_eq!(suite.treasury(), coins(20, DENOM));
+
+    suite
+        .execute(
+            BOB,
+            ExecuteMsg::TipAuthor {
+                token_id: "1".to_string(),
+            },
+            &coins(1_000, DENOM),
+        )
+        .unwrap();
+    assert_eq!(suite.balance(ALICE), 10_770);
+    assert_eq!(suite.balance("writer"), 110);
+    assert_eq!(suite.treasury(), coins(220, DENOM));
+}
+
+#[test]
+fn raised_platform_fee_comes_out_of_the_owner_share_first() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetRevenueSplit {
+                token_id: "1".to_string(),
+                split: Some(RevenueSplitMsg {
+                    author: "writer".to_string(),
+                    owner_bps: 1_000,
+                    author_bps: 9_000,
+                    platform_bps: 0,
+                }),
+            },
+            &[],
+        )
+        .unwrap();
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateFeeConfig {
+                platform_fee_bps: 2_000,
+                late_fee: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    // The owner share only covers half of the fee, the author pays the rest
+    let info: RoyaltyInfoResponse = suite.query(QueryMsg::GetRoyaltyInfo {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(
+        (info.owner_bps, info.author_bps, info.platform_bps),
+        (0, 8_000, 2_000)
+    );
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_000);
+    assert_eq!(suite.balance("writer"), 80);
+    assert_eq!(suite.treasury(), coins(20, DENOM));
+}
+
+#[test]
+fn hold_queue_is_capped() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    let place_hold = ExecuteMsg::PlaceHold {
+        token_id: "1".to_string(),
+    };
+    for member in 0..MAX_HOLDS {
+        suite
+            .execute(&format!("member{}", member), place_hold.clone(), &[])
+            .unwrap();
+    }
+
+    let err = suite.execute(ALICE, place_hold, &[]).unwrap_err();
+    assert!(matches!(err, ContractError::HoldQueueFull { max: MAX_HOLDS }), "{:?}", err);
+}
+
+#[test]
+fn royalties_need_a_percentage_and_an_address() {
+    let mut suite = Suite::new();
+    let mint = |token_id: &str, royalty_percentage, royalty_payment_address: Option<&str>| ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: ALICE.to_string(),
+        metadata: Metadata {
+            title: "Book".to_string(),
+            royalty_percentage,
+            royalty_payment_address: royalty_payment_address.map(str::to_string),
+            ..Default::default()
+        },
+    };
+    let err = suite.execute(MINTER, mint("1", Some(10), None), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::IncompleteRoyalty {}), "{:?}", err);
+    let err = suite.execute(MINTER, mint("1", None, Some("writer")), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::IncompleteRoyalty {}), "{:?}", err);
+
+    suite.execute(MINTER, mint("1", Some(10), Some("writer")), &[]).unwrap();
+    let royalties: RoyaltiesInfoResponse = suite.query(QueryMsg::Extension {
+        msg: Cw2981QueryMsg::RoyaltyInfo {
+            token_id: "1".to_string(),
+            sale_price: Uint128::new(1_000),
+        },
+    });
+    assert_eq!(royalties.address, "writer");
+    assert_eq!(royalties.royalty_amount, Uint128::new(100));
+}
+
+#[test]
+fn returning_a_book_not_on_loan_is_refused() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+
+    let err = suite.return_book(BOB, "1", &[]).unwrap_err();
+    assert!(
+        matches!(&err, ContractError::LoanNotFound { token_id } if token_id == "1"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn delegates_borrow_for_borrowers_who_approved_them() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.mint("2", ALICE);
+    let borrow_for = |token_id: &str| ExecuteMsg::BorrowBookFor {
+        token_id: token_id.to_string(),
+        borrower: BOB.to_string(),
+    };
+
+    let err = suite.execute("delegate", borrow_for("1"), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+
+    suite
+        .execute(
+            BOB,
+            ExecuteMsg::ApproveBorrowDelegate {
+                delegate: "delegate".to_string(),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+    let delegates: cw721::OperatorsResponse = suite.query(QueryMsg::BorrowDelegates {
+        borrower: BOB.to_string(),
+        include_expired: None,
+        start_after: None,
+        limit: None,
+    });
+    assert_eq!(delegates.operators.len(), 1);
+    assert_eq!(delegates.operators[0].spender, "delegate");
+    suite.execute("delegate", borrow_for("1"), &[]).unwrap();
+    let loan: Option<LoanResponse> = suite.query(QueryMsg::GetLoan {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(loan.unwrap().borrower, BOB);
+
+    suite
+        .execute(
+            BOB,
+            ExecuteMsg::RevokeBorrowDelegate {
+                delegate: "delegate".to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+    let err = suite.execute("delegate", borrow_for("2"), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+}
+
+#[test]
+fn expired_delegate_approvals_no_longer_borrow() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let approve = |expires| ExecuteMsg::ApproveBorrowDelegate {
+        delegate: "delegate".to_string(),
+        expires: Some(expires),
+    };
+    let height = suite.app.block_info().height;
+
+    let err = suite
+        .execute(BOB, approve(Expiration::AtHeight(height)), &[])
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Expired {}), "{:?}", err);
+    suite
+        .execute(BOB, approve(Expiration::AtHeight(height + 1)), &[])
+        .unwrap();
+
+    suite.advance_days(1);
+    let err = suite
+        .execute(
+            "delegate",
+            ExecuteMsg::BorrowBookFor {
+                token_id: "1".to_string(),
+                borrower: BOB.to_string(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+}
+
+#[test]
+fn only_the_minter_mints() {
+    let mut suite = Suite::new();
+    let mint = ExecuteMsg::Mint {
+        token_id: "1".to_string(),
+        owner: ALICE.to_string(),
+        metadata: Metadata::default(),
+    };
+
+    let err = suite.execute(BOB, mint.clone(), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite.execute(MINTER, mint, &[]).unwrap();
+    let owner: cw721::OwnerOfResponse = suite.query(QueryMsg::OwnerOf {
+        token_id: "1".to_string(),
+        include_expired: None,
+    });
+    assert_eq!(owner.owner, ALICE);
+}
+
+#[test]
+fn only_the_minter_adds_books() {
+    let mut suite = Suite::new();
+
+    let err = suite.add_book(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite.add_book(MINTER, "1").unwrap();
+    let book: BookResponse = suite.query(QueryMsg::GetBook {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(book.book.owner, ALICE);
+}
+
+#[test]
+fn minter_hands_over_the_role() {
+    let mut suite = Suite::new();
+    let update = ExecuteMsg::UpdateMinter {
+        new_minter: BOB.to_string(),
+    };
+
+    let err = suite.execute(ALICE, update.clone(), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite.execute(MINTER, update, &[]).unwrap();
+    let err = suite.add_book(MINTER, "1").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite.add_book(BOB, "1").unwrap();
+}
+
+#[test]
+fn loans_are_due_after_the_default_loan_period() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let now = suite.app.block_info().time;
+
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    let loan = suite.loan("1").unwrap();
+    assert_eq!(loan.borrower, BOB);
+    assert_eq!(loan.start_time, now);
+    assert_eq!(loan.due, Expiration::AtTime(now.plus_seconds(14 * DAY)));
+    assert_eq!(loan.renewals, 0);
+    assert!(!loan.overdue);
+    assert_eq!(suite.loan("2"), None);
+}
+
+#[test]
+fn book_loan_period_overrides_the_default() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let set_loan_period = |loan_period| ExecuteMsg::SetLoanPeriod {
+        token_id: "1".to_string(),
+        loan_period: Some(loan_period),
+    };
+
+    let err = suite.execute(BOB, set_loan_period(Duration::Time(7 * DAY)), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    let err = suite.execute(ALICE, set_loan_period(Duration::Height(100)), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::MismatchedDurations {}), "{:?}", err);
+    suite.execute(ALICE, set_loan_period(Duration::Time(7 * DAY)), &[]).unwrap();
+
+    let now = suite.app.block_info().time;
+    suite.borrow(BOB, "1", &[]).unwrap();
+    assert_eq!(suite.loan("1").unwrap().due, Expiration::AtTime(now.plus_seconds(7 * DAY)));
+}
+
+#[test]
+fn overdue_loans_are_listed_after_the_due_date() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.mint("2", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(7);
+    suite.borrow(BOB, "2", &[]).unwrap();
+    let overdue = |suite: &Suite| -> Vec<String> {
+        suite
+            .query::<LoansResponse>(QueryMsg::GetOverdueLoans {
+                start_after: None,
+                limit: None,
+            })
+            .loans
+            .into_iter()
+            .map(|loan| loan.token_id)
+            .collect()
+    };
+
+    assert!(overdue(&suite).is_empty());
+    suite.advance_days(8);
+    assert_eq!(overdue(&suite), vec!["1"]);
+    assert!(suite.loan("1").unwrap().overdue);
+    assert!(!suite.loan("2").unwrap().overdue);
+    suite.advance_days(7);
+    assert_eq!(overdue(&suite), vec!["1", "2"]);
+}
+
+#[test]
+fn renewing_extends_the_due_date_by_the_loan_period() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    let now = suite.app.block_info().time;
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(10);
+
+    let response = suite.renew(BOB, "1").unwrap();
+
+    let due = Expiration::AtTime(now.plus_seconds(28 * DAY));
+    let loan = suite.loan("1").unwrap();
+    assert_eq!(loan.due, due);
+    assert_eq!(loan.renewals, 1);
+    let event = response.events.iter().find(|e| e.ty == "wasm").unwrap();
+    assert!(event.attributes.iter().any(|a| a.key == "due" && a.value == due.to_string()));
+    assert!(event.attributes.iter().any(|a| a.key == "renewals" && a.value == "1"));
+}
+
+#[test]
+fn renewals_are_capped() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    suite.renew(BOB, "1").unwrap();
+    suite.renew(BOB, "1").unwrap();
+    let err = suite.renew(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::MaxRenewalsReached { max: 2 }), "{:?}", err);
+    assert_eq!(suite.loan("1").unwrap().renewals, 2);
+}
+
+#[test]
+fn books_with_holds_cannot_be_renewed() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite
+        .execute(
+            ALICE,
+            ExecuteMsg::PlaceHold {
+                token_id: "1".to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let err = suite.renew(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::BookReserved {}), "{:?}", err);
+}
+
+#[test]
+fn only_the_borrower_renews() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+
+    let err = suite.renew(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::LoanNotFound { .. }), "{:?}", err);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    let err = suite.renew(ALICE, "1").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+}
+
+#[test]
+fn overdue_loans_accruing_a_late_fee_cannot_be_renewed() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_late_fee(ALICE, "1", 10, DAY);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.advance_days(15);
+
+    let err = suite.renew(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::LoanOverdue {}), "{:?}", err);
+}
+
+#[test]
+fn returned_books_are_reserved_for_the_first_holder() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.place_hold("carol", "1").unwrap();
+    suite.place_hold("dave", "1").unwrap();
+    let holders = |holds: &HoldsResponse| -> Vec<String> {
+        holds.holds.iter().map(|hold| hold.holder.to_string()).collect()
+    };
+    let holds = suite.holds("1");
+    assert_eq!(holds.reservation, None);
+    assert_eq!(holders(&holds), vec!["carol", "dave"]);
+
+    suite.return_book(BOB, "1", &[]).unwrap();
+
+    let now = suite.app.block_info().time;
+    let holds = suite.holds("1");
+    assert_eq!(
+        holds.reservation,
+        Some(Reservation {
+            holder: Addr::unchecked("carol"),
+            expires: Expiration::AtTime(now.plus_seconds(3 * DAY)),
+        })
+    );
+    assert_eq!(holders(&holds), vec!["dave"]);
+    let err = suite.borrow("dave", "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::BookReserved {}), "{:?}", err);
+    suite.borrow("carol", "1", &[]).unwrap();
+    let holds = suite.holds("1");
+    assert_eq!(holds.reservation, None);
+    assert_eq!(holders(&holds), vec!["dave"]);
+}
+
+#[test]
+fn reservations_pass_to_the_next_holder_after_the_pickup_window() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.place_hold("carol", "1").unwrap();
+    suite.place_hold("dave", "1").unwrap();
+    suite.return_book(BOB, "1", &[]).unwrap();
+
+    suite.advance_days(4);
+
+    let reservation = suite.holds("1").reservation.unwrap();
+    assert_eq!(reservation.holder, "dave");
+    let err = suite.borrow("carol", "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::BookReserved {}), "{:?}", err);
+    suite.borrow("dave", "1", &[]).unwrap();
+}
+
+#[test]
+fn cancelling_a_reservation_hands_the_book_to_the_next_holder() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    suite.place_hold("carol", "1").unwrap();
+    suite.place_hold("dave", "1").unwrap();
+    suite.return_book(BOB, "1", &[]).unwrap();
+    let cancel_hold = ExecuteMsg::CancelHold {
+        token_id: "1".to_string(),
+    };
+
+    let err = suite.execute(BOB, cancel_hold.clone(), &[]).unwrap_err();
+    assert!(matches!(err, ContractError::HoldNotFound {}), "{:?}", err);
+    suite.execute("carol", cancel_hold, &[]).unwrap();
+
+    let holds = suite.holds("1");
+    assert_eq!(holds.reservation.unwrap().holder, "dave");
+    assert!(holds.holds.is_empty());
+}
+
+#[test]
+fn holds_are_only_placed_on_books_out_of_reach() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+
+    let err = suite.place_hold(BOB, "2").unwrap_err();
+    assert!(matches!(err, ContractError::TokenNotFound {}), "{:?}", err);
+    let err = suite.place_hold(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::BookAvailable {}), "{:?}", err);
+    suite.borrow(BOB, "1", &[]).unwrap();
+    let err = suite.place_hold(BOB, "1").unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyOnHold {}), "{:?}", err);
+    suite.place_hold("carol", "1").unwrap();
+    let err = suite.place_hold("carol", "1").unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyOnHold {}), "{:?}", err);
+}
+
+#[test]
+fn accepted_requests_lend_the_book_and_pay_the_owner() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_price(ALICE, "1", 100);
+    suite.set_policy(ALICE, "1", LendingPolicy::ApprovalRequired);
+
+    suite.borrow(BOB, "1", &coins(100, DENOM)).unwrap();
+    assert_eq!(suite.loan("1"), None);
+    let by_owner: BorrowRequestsResponse = suite.query(QueryMsg::BorrowRequestsByOwner {
+        owner: ALICE.to_string(),
+        start_after: None,
+        limit: None,
+    });
+    let by_requester: BorrowRequestsResponse = suite.query(QueryMsg::BorrowRequestsByRequester {
+        requester: BOB.to_string(),
+        start_after: None,
+        limit: None,
+    });
+    assert_eq!(by_owner, by_requester);
+    assert_eq!(by_owner.requests.len(), 1);
+    let request = &by_owner.requests[0];
+    assert_eq!(request.request_id, 1);
+    assert_eq!(request.token_id, "1");
+    assert_eq!(request.requester, BOB);
+    assert_eq!(request.owner, ALICE);
+    assert_eq!(request.payment, Some(Coin::new(100, DENOM)));
+
+    let err = suite
+        .execute(BOB, ExecuteMsg::AcceptBorrowRequest { request_id: 1 }, &[])
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+    suite
+        .execute(ALICE, ExecuteMsg::AcceptBorrowRequest { request_id: 1 }, &[])
+        .unwrap();
+
+    assert_eq!(suite.loan("1").unwrap().borrower, BOB);
+    assert_eq!(suite.balance(ALICE), 10_100);
+    let by_owner: BorrowRequestsResponse = suite.query(QueryMsg::BorrowRequestsByOwner {
+        owner: ALICE.to_string(),
+        start_after: None,
+        limit: None,
+    });
+    assert!(by_owner.requests.is_empty());
+}
+
+#[test]
+fn members_only_books_are_lent_to_members() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_policy(ALICE, "1", LendingPolicy::MembersOnly);
+
+    let err = suite.borrow(BOB, "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::MembersOnly {}), "{:?}", err);
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::GrantRole {
+                role: Role::Member,
+                address: BOB.to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+    suite.borrow(BOB, "1", &[]).unwrap();
+}
+
+#[test]
+fn closed_books_are_only_taken_by_their_owner() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.set_policy(ALICE, "1", LendingPolicy::Closed);
+
+    let err = suite.borrow(BOB, "1", &[]).unwrap_err();
+    assert!(matches!(err, ContractError::LendingClosed {}), "{:?}", err);
+    suite.borrow(ALICE, "1", &[]).unwrap();
+}
+
+#[test]
+fn books_on_loan_are_not_transferred_or_burned() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    let err = suite.transfer(ALICE, "1", "carol").unwrap_err();
+    assert!(matches!(err, ContractError::BookOnLoan {}), "{:?}", err);
+    let err = suite.burn(ALICE, "1").unwrap_err();
+    assert!(matches!(err, ContractError::BookOnLoan {}), "{:?}", err);
+
+    suite.return_book(BOB, "1", &[]).unwrap();
+    suite.transfer(ALICE, "1", "carol").unwrap();
+    suite.burn("carol", "1").unwrap();
+}
+
+#[test]
+fn loans_follow_the_book_when_transfers_of_loans_are_allowed() {
+    let mut suite = Suite::with(InstantiateMsg {
+        transfer_loans: Some(true),
+        ..instantiate_msg()
+    });
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    suite.transfer(ALICE, "1", "carol").unwrap();
+
+    let book: BookResponse = suite.query(QueryMsg::GetBook {
+        token_id: "1".to_string(),
+    });
+    assert_eq!(book.book.owner, "carol");
+    assert_eq!(book.borrower, Some(Addr::unchecked(BOB)));
+    assert_eq!(suite.loan("1").unwrap().borrower, BOB);
+    let err = suite.burn("carol", "1").unwrap_err();
+    assert!(matches!(err, ContractError::BookOnLoan {}), "{:?}", err);
+}
+
+#[test]
+fn tokens_are_listed_by_owner_a_page_at_a_time() {
+    let mut suite = Suite::new();
+    for token_id in ["1", "2", "3"] {
+        suite.mint(token_id, ALICE);
+    }
+    suite.mint("4", BOB);
+    suite.transfer(ALICE, "2", BOB).unwrap();
+    let tokens = |suite: &Suite, owner: &str, start_after: Option<&str>| -> Vec<String> {
+        suite
+            .query::<cw721::TokensResponse>(QueryMsg::Tokens {
+                owner: owner.to_string(),
+                start_after: start_after.map(str::to_string),
+                limit: Some(1),
+            })
+            .tokens
+    };
+
+    assert_eq!(tokens(&suite, ALICE, None), vec!["1"]);
+    assert_eq!(tokens(&suite, ALICE, Some("1")), vec!["3"]);
+    assert!(tokens(&suite, ALICE, Some("3")).is_empty());
+    assert_eq!(tokens(&suite, BOB, None), vec!["2"]);
+    assert_eq!(tokens(&suite, BOB, Some("2")), vec!["4"]);
+}
+
+#[test]
+fn migrating_from_0_1_0_indexes_tokens_by_owner() {
+    let mut deps = baseline_deps();
+    baseline_token(&mut deps.storage, "1", ALICE);
+    baseline_token(&mut deps.storage, "2", BOB);
+    baseline_token(&mut deps.storage, "3", ALICE);
+    BASELINE_NUM_TOKENS.save(&mut deps.storage, &3).unwrap();
+
+    migrate_from_baseline(&mut deps);
+
+    let tokens = |owner: &str| -> Vec<String> {
+        query_deps::<cw721::TokensResponse>(
+            &deps,
+            QueryMsg::Tokens {
+                owner: owner.to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .tokens
+    };
+    assert_eq!(tokens(ALICE), vec!["1", "3"]);
+    assert_eq!(tokens(BOB), vec!["2"]);
+}
+
+#[test]
+fn book_lists_return_the_cursor_of_the_next_page() {
+    let mut suite = Suite::new();
+    for token_id in ["1", "2", "3", "4"] {
+        suite.mint(token_id, ALICE);
+    }
+    suite.borrow(BOB, "2", &[]).unwrap();
+    let page = |suite: &Suite, msg: QueryMsg| -> (Vec<String>, Option<String>) {
+        let response: BooksResponse = suite.query(msg);
+        let token_ids = response.books.into_iter().map(|book| book.token_id).collect();
+        (token_ids, response.next_start_after)
+    };
+    let all = |start_after: Option<&str>| QueryMsg::GetAllBooks {
+        start_after: start_after.map(str::to_string),
+        limit: Some(2),
+    };
+    let available = |start_after: Option<&str>| QueryMsg::GetAvailableBooks {
+        start_after: start_after.map(str::to_string),
+        limit: Some(2),
+    };
+
+    assert_eq!(page(&suite, all(None)), (vec!["1".to_string(), "2".to_string()], Some("2".to_string())));
+    assert_eq!(page(&suite, all(Some("2"))), (vec!["3".to_string(), "4".to_string()], None));
+    assert_eq!(page(&suite, available(None)), (vec!["1".to_string(), "3".to_string()], Some("3".to_string())));
+    assert_eq!(page(&suite, available(Some("3"))), (vec!["4".to_string()], None));
+}
+
+#[test]
+fn borrowed_book_lists_return_the_cursor_of_the_next_page() {
+    let mut suite = Suite::new();
+    for token_id in ["1", "2", "3"] {
+        suite.mint(token_id, ALICE);
+        suite.borrow(BOB, token_id, &[]).unwrap();
+    }
+    suite.mint("4", ALICE);
+    suite.borrow(ALICE, "4", &[]).unwrap();
+    let page = |suite: &Suite, msg: QueryMsg| -> (Vec<String>, Option<String>) {
+        let response: BooksResponse = suite.query(msg);
+        let token_ids = response.books.into_iter().map(|book| book.token_id).collect();
+        (token_ids, response.next_start_after)
+    };
+    let borrowed = |start_after: Option<&str>| QueryMsg::GetBorrowedBooks {
+        start_after: start_after.map(str::to_string),
+        limit: Some(3),
+    };
+    let borrowed_by_bob = |start_after: Option<&str>| QueryMsg::GetMyBorrowedBooks {
+        borrower: Addr::unchecked(BOB),
+        start_after: start_after.map(str::to_string),
+        limit: Some(2),
+    };
+
+    let (token_ids, next) = page(&suite, borrowed(None));
+    assert_eq!((token_ids.len(), next.as_deref()), (3, Some("3")));
+    assert_eq!(page(&suite, borrowed(Some("3"))), (vec!["4".to_string()], None));
+    assert_eq!(page(&suite, borrowed_by_bob(None)), (vec!["1".to_string(), "2".to_string()], Some("2".to_string())));
+    assert_eq!(page(&suite, borrowed_by_bob(Some("2"))), (vec!["3".to_string()], None));
+}
+
+#[test]
+fn migrating_from_0_1_0_indexes_loans_by_borrower() {
+    let mut deps = baseline_deps();
+    for token_id in ["1", "2", "3"] {
+        baseline_token(&mut deps.storage, token_id, ALICE);
+    }
+    BASELINE_BORROWERS.save(&mut deps.storage, "1", &Addr::unchecked(BOB)).unwrap();
+    BASELINE_BORROWERS.save(&mut deps.storage, "2", &Addr::unchecked("carol")).unwrap();
+    BASELINE_BORROWERS.save(&mut deps.storage, "3", &Addr::unchecked(BOB)).unwrap();
+    BASELINE_NUM_TOKENS.save(&mut deps.storage, &3).unwrap();
+
+    migrate_from_baseline(&mut deps);
+
+    let borrowed = |borrower: &str| -> Vec<String> {
+        query_deps::<BooksResponse>(
+            &deps,
+            QueryMsg::GetMyBorrowedBooks {
+                borrower: Addr::unchecked(borrower),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .books
+        .into_iter()
+        .map(|book| book.token_id)
+        .collect()
+    };
+    assert_eq!(borrowed(BOB), vec!["1", "3"]);
+    assert_eq!(borrowed("carol"), vec!["2"]);
+}
+
+#[test]
+fn pausing_payments_stops_tips_fee_payments_and_price_changes() {
+    let mut suite = Suite::new();
+    let token = suite.instantiate_token();
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::UpdateAcceptedCw20 {
+                add: vec![token.to_string()],
+                remove: vec![],
+            },
+            &[],
+        )
+        .unwrap();
+    suite.mint("1", ALICE);
+    suite
+        .execute(
+            OWNER,
+            ExecuteMsg::Pause {
+                categories: Some(vec![PauseCategory::Payments]),
+            },
+            &[],
+        )
+        .unwrap();
+    let tip = ExecuteMsg::TipAuthor {
+        token_id: "1".to_string(),
+    };
+
+    let err = suite.execute(BOB, tip.clone(), &coins(100, DENOM)).unwrap_err();
+    assert!(matches!(&err, ContractError::Paused { category } if category == "payments"), "{:?}", err);
+    let err = suite.execute(BOB, ExecuteMsg::PayFees {}, &coins(100, DENOM)).unwrap_err();
+    assert!(matches!(err, ContractError::Paused { .. }), "{:?}", err);
+    let err = suite
+        .send_tokens(
+            &token,
+            BOB,
+            100,
+            ReceiveMsg::TipAuthor {
+                token_id: "1".to_string(),
+            },
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Paused { .. }), "{:?}", err);
+    assert_eq!(suite.token_balance(&token, BOB), 10_000);
+    let err = suite
+        .execute(
+            ALICE,
+            ExecuteMsg::SetBorrowPrice {
+                token_id: "1".to_string(),
+                price: Some(Coin::new(100, DENOM)),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Paused { .. }), "{:?}", err);
+    assert_eq!(suite.balance(BOB), 10_000);
+
+    suite.execute(OWNER, ExecuteMsg::Unpause { categories: None }, &[]).unwrap();
+    suite.execute(BOB, tip, &coins(100, DENOM)).unwrap();
+    assert_eq!(suite.balance(ALICE), 10_100);
+}
+
+#[test]
+fn unauthorized_sends_of_books_on_loan_are_refused_as_unauthorized() {
+    let mut suite = Suite::new();
+    suite.mint("1", ALICE);
+    suite.borrow(BOB, "1", &[]).unwrap();
+
+    let err = suite
+        .execute(
+            BOB,
+            ExecuteMsg::SendNft {
+                contract: "receiver".to_string(),
+                token_id: "1".to_string(),
+                msg: Binary::default(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}), "{:?}", err);
+}
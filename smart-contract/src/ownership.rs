@@ -0,0 +1,228 @@
+use std::fmt::Display;
+
+use cosmwasm_std::{Addr, Attribute, BlockInfo, Deps, DepsMut, StdError, StdResult, Storage};
+use cw_storage_plus::Item;
+use cw721::Expiration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// Two-step contract ownership, storage layout and messages follow the cw-ownable conventions
+// so that wallets and explorers recognise the `update_ownership` / `ownership` messages.
+
+const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+
+/// The contract's ownership info
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ownership {
+    /// The contract's current owner.
+    /// `None` if the ownership has been renounced.
+    pub owner: Option<Addr>,
+    /// The account who has been proposed to take over the ownership.
+    /// `None` if there isn't a pending ownership transfer.
+    pub pending_owner: Option<Addr>,
+    /// The deadline for the pending owner to accept the ownership.
+    /// `None` if there isn't a pending ownership transfer, or if a transfer
+    /// exists and it doesn't have a deadline.
+    pub pending_expiry: Option<Expiration>,
+}
+
+/// Actions that can be taken to alter the contract's ownership
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Propose to transfer the contract's ownership to another account,
+    /// optionally with an expiry time.
+    ///
+    /// Can only be called by the contract's current owner.
+    ///
+    /// Any existing pending ownership transfer is overwritten.
+    TransferOwnership {
+        new_owner: String,
+        expiry: Option<Expiration>,
+    },
+    /// Accept the pending ownership transfer.
+    ///
+    /// Can only be called by the pending owner.
+    AcceptOwnership,
+    /// Give up the contract's ownership and the possibility of appointing
+    /// a new owner.
+    ///
+    /// Can only be invoked by the contract's current owner.
+    ///
+    /// Any existing pending ownership transfer is canceled.
+    RenounceOwnership,
+}
+
+/// Errors associated with the contract's ownership
+#[derive(Error, Debug, PartialEq)]
+pub enum OwnershipError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Contract ownership has been renounced")]
+    NoOwner,
+
+    #[error("Caller is not the contract's current owner")]
+    NotOwner,
+
+    #[error("Caller is not the contract's pending owner")]
+    NotPendingOwner,
+
+    #[error("There isn't a pending ownership transfer")]
+    TransferNotFound,
+
+    #[error("A pending ownership transfer exists but it has expired")]
+    TransferExpired,
+
+    #[error("Cannot set an ownership transfer deadline that is already expired")]
+    InvalidExpiry,
+}
+
+impl Ownership {
+    /// Serializes the current ownership state as attributes which may
+    /// be used in a message response.
+    pub fn into_attributes(self) -> Vec<Attribute> {
+        vec![
+            Attribute::new("owner", none_or(self.owner.as_ref())),
+            Attribute::new("pending_owner", none_or(self.pending_owner.as_ref())),
+            Attribute::new("pending_expiry", none_or(self.pending_expiry.as_ref())),
+        ]
+    }
+
+    /// Assert that an account is the contract's current owner.
+    pub fn assert_owner(&self, sender: &Addr) -> Result<(), OwnershipError> {
+        match &self.owner {
+            None => Err(OwnershipError::NoOwner),
+            Some(owner) if sender != owner => Err(OwnershipError::NotOwner),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+fn none_or<T: Display>(value: Option<&T>) -> String {
+    value.map_or_else(|| "none".to_string(), |v| v.to_string())
+}
+
+/// Set the given address as the contract owner.
+///
+/// This function is only intended to be used during contract instantiation.
+pub fn initialize_owner(storage: &mut dyn Storage, owner: &Addr) -> StdResult<Ownership> {
+    let ownership = Ownership {
+        owner: Some(owner.clone()),
+        pending_owner: None,
+        pending_expiry: None,
+    };
+    OWNERSHIP.save(storage, &ownership)?;
+    Ok(ownership)
+}
+
+/// Whether the ownership info has been stored yet.
+pub fn is_initialized(storage: &dyn Storage) -> bool {
+    OWNERSHIP.exists(storage)
+}
+
+/// Return the contract's current ownership info.
+pub fn get_ownership(storage: &dyn Storage) -> StdResult<Ownership> {
+    OWNERSHIP.load(storage)
+}
+
+/// Assert that an account is the contract's current owner.
+pub fn assert_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), OwnershipError> {
+    get_ownership(storage)?.assert_owner(sender)
+}
+
+/// Query handler for the `ownership` query
+pub fn query_ownership(deps: Deps) -> StdResult<Ownership> {
+    get_ownership(deps.storage)
+}
+
+/// Update the contract's ownership info based on the given action.
+/// Return the updated ownership.
+pub fn update_ownership(
+    deps: DepsMut,
+    block: &BlockInfo,
+    sender: &Addr,
+    action: Action,
+) -> Result<Ownership, OwnershipError> {
+    match action {
+        Action::TransferOwnership { new_owner, expiry } => {
+            transfer_ownership(deps, block, sender, &new_owner, expiry)
+        }
+        Action::AcceptOwnership => accept_ownership(deps.storage, block, sender),
+        Action::RenounceOwnership => renounce_ownership(deps.storage, sender),
+    }
+}
+
+/// Propose to transfer the contract's ownership to the given address, with an
+/// optional deadline.
+fn transfer_ownership(
+    deps: DepsMut,
+    block: &BlockInfo,
+    sender: &Addr,
+    new_owner: &str,
+    expiry: Option<Expiration>,
+) -> Result<Ownership, OwnershipError> {
+    let mut ownership = OWNERSHIP.load(deps.storage)?;
+    ownership.assert_owner(sender)?;
+
+    let new_owner = deps.api.addr_validate(new_owner)?;
+
+    // Reject an expiry that has already passed
+    if let Some(expiry) = &expiry {
+        if expiry.is_expired(block) {
+            return Err(OwnershipError::InvalidExpiry);
+        }
+    }
+
+    ownership.pending_owner = Some(new_owner);
+    ownership.pending_expiry = expiry;
+    OWNERSHIP.save(deps.storage, &ownership)?;
+
+    Ok(ownership)
+}
+
+/// Accept a pending ownership transfer.
+fn accept_ownership(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+) -> Result<Ownership, OwnershipError> {
+    let mut ownership = OWNERSHIP.load(storage)?;
+
+    let pending_owner = ownership
+        .pending_owner
+        .take()
+        .ok_or(OwnershipError::TransferNotFound)?;
+
+    if *sender != pending_owner {
+        return Err(OwnershipError::NotPendingOwner);
+    }
+
+    if let Some(expiry) = ownership.pending_expiry.take() {
+        if expiry.is_expired(block) {
+            return Err(OwnershipError::TransferExpired);
+        }
+    }
+
+    ownership.owner = Some(pending_owner);
+    OWNERSHIP.save(storage, &ownership)?;
+
+    Ok(ownership)
+}
+
+/// Set the contract's ownership as vacant permanently.
+fn renounce_ownership(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+) -> Result<Ownership, OwnershipError> {
+    let mut ownership = OWNERSHIP.load(storage)?;
+    ownership.assert_owner(sender)?;
+
+    ownership.owner = None;
+    ownership.pending_owner = None;
+    ownership.pending_expiry = None;
+    OWNERSHIP.save(storage, &ownership)?;
+
+    Ok(ownership)
+}
@@ -1,31 +1,208 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Coin, Empty, StdResult, Storage, Timestamp, Uint128};
 use cw721::{ContractInfoResponse, Expiration};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::Duration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-// Struct to store book metadata
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+// Book metadata together with its owner, as returned by the legacy queries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Book {
     pub title: String,
     pub author: String,
     pub url: String,
+    /// Authors after the first one
+    #[serde(default)]
+    pub co_authors: Vec<String>,
+    #[serde(default)]
+    pub isbn: Option<String>,
+    #[serde(default)]
+    pub publication_year: Option<u32>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub page_count: Option<u32>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// URL of the cover image
+    #[serde(default)]
+    pub image: Option<String>,
     pub owner: Addr,
 }
 
+impl From<TokenInfo> for Book {
+    fn from(token: TokenInfo) -> Self {
+        Book {
+            title: token.metadata.title,
+            author: token.metadata.author,
+            url: token.metadata.url,
+            co_authors: token.metadata.co_authors,
+            isbn: token.metadata.isbn,
+            publication_year: token.metadata.publication_year,
+            genres: token.metadata.genres,
+            publisher: token.metadata.publisher,
+            language: token.metadata.language,
+            page_count: token.metadata.page_count,
+            description: token.metadata.description,
+            image: token.metadata.image,
+            owner: token.owner,
+        }
+    }
+}
+
 // Define CW721 token extension
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
 pub struct Metadata {
     pub title: String,
     pub author: String,
     pub url: String,
+    /// Authors after the first one
+    #[serde(default)]
+    pub co_authors: Vec<String>,
+    #[serde(default)]
+    pub isbn: Option<String>,
+    #[serde(default)]
+    pub publication_year: Option<u32>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub page_count: Option<u32>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// URL of the cover image
+    #[serde(default)]
+    pub image: Option<String>,
+    /// CW2981 royalty owed on secondary sales, as a percentage of the sale price (0 to 100)
+    #[serde(default)]
+    pub royalty_percentage: Option<u64>,
+    /// CW2981 royalty recipient
+    #[serde(default)]
+    pub royalty_payment_address: Option<String>,
+}
+
+// Contract state and configuration. Kept apart from "contract_info", where cw2 stores the contract version
+pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("nft_info");
+// Address allowed to create new tokens (Mint / AddBook)
+pub const MINTER: Item<Addr> = Item::new("minter");
+
+// Role registry, keyed by (role, holder)
+pub const ROLES: Map<(&str, &Addr), Empty> = Map::new("roles");
+// Roles allowed to perform each gated action, keyed by action name
+pub const ROLE_POLICY: Map<&str, Vec<Role>> = Map::new("role_policy");
+
+// Staff and member roles that can be granted by the contract admins
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Librarian,
+    Curator,
+    Member,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Admin, Role::Librarian, Role::Curator, Role::Member];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Librarian => "librarian",
+            Role::Curator => "curator",
+            Role::Member => "member",
+        }
+    }
+}
+
+// Actions open to admins and librarians by default
+pub const STAFF_ACTIONS: [&str; 3] = ["mint", "add_book", "force_return"];
+
+// Categories of state-changing messages currently paused by the circuit breaker
+pub const PAUSED: Item<Vec<PauseCategory>> = Item::new("paused");
+
+// Groups of messages that can be paused independently
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseCategory {
+    /// Transfers, sends and approvals, revoking approvals is never paused
+    Transfers,
+    /// Token creation and destruction (mint, add book, burn)
+    Mints,
+    /// Borrowing books
+    Borrows,
+    /// Returning books
+    Returns,
+    /// Tips, late fee payments and borrow price changes
+    Payments,
+}
+
+impl PauseCategory {
+    pub const ALL: [PauseCategory; 5] = [
+        PauseCategory::Transfers,
+        PauseCategory::Mints,
+        PauseCategory::Borrows,
+        PauseCategory::Returns,
+        PauseCategory::Payments,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PauseCategory::Transfers => "transfers",
+            PauseCategory::Mints => "mints",
+            PauseCategory::Borrows => "borrows",
+            PauseCategory::Returns => "returns",
+            PauseCategory::Payments => "payments",
+        }
+    }
+}
+
+// Actions that can be gated by the role policy.
+// For `mint` and `add_book` the policy lists the roles allowed to create tokens besides the minter,
+// `force_return` lists the roles allowed to return a book on behalf of its borrower,
+// for every other action it lists the roles required to call it (empty means open to everyone).
+pub const POLICY_ACTIONS: [&str; 15] = [
+    "transfer_nft",
+    "send_nft",
+    "approve",
+    "revoke",
+    "approve_all",
+    "revoke_all",
+    "mint",
+    "burn",
+    "add_book",
+    "borrow_book",
+    "return_book",
+    "renew_loan",
+    "place_hold",
+    "cancel_hold",
+    "force_return",
+];
+
+// Maps for token ownership and approvals, tokens are indexed by owner
+pub struct TokenIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, TokenInfo, String>,
 }
 
-// Contract state and configuration
-pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("contract_info");
+impl<'a> IndexList<TokenInfo> for TokenIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<TokenInfo>> + '_> {
+        let v: Vec<&dyn Index<TokenInfo>> = vec![&self.owner];
+        Box::new(v.into_iter())
+    }
+}
 
-// Maps for token ownership and approvals
-pub const TOKENS: Map<&str, TokenInfo> = Map::new("tokens");
+pub fn tokens<'a>() -> IndexedMap<'a, &'a str, TokenInfo, TokenIndexes<'a>> {
+    let indexes = TokenIndexes {
+        owner: MultiIndex::new(|_pk, t| t.owner.clone(), "tokens", "tokens__owner"),
+    };
+    IndexedMap::new("tokens", indexes)
+}
 pub const OPERATORS: Map<(&Addr, &Addr), Expiration> = Map::new("operators");
 pub const NUM_TOKENS: Item<u64> = Item::new("num_tokens");
 
@@ -38,6 +215,78 @@ pub struct TokenInfo {
     pub approvals: Vec<Approval>,
     // Book metadata
     pub metadata: Metadata,
+    // Lending terms set by the owner
+    #[serde(default)]
+    pub terms: LendingTerms,
+    // How borrow fees and tips are shared with the author and the platform
+    #[serde(default)]
+    pub revenue_split: Option<RevenueSplit>,
+}
+
+// Shares of the borrow fees and tips of a book, in basis points summing to 10000.
+// Without one, the owner gets everything but the contract-wide platform fee.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RevenueSplit {
+    /// Address the author's share is paid to
+    pub author: Addr,
+    pub owner_bps: u16,
+    pub author_bps: u16,
+    pub platform_bps: u16,
+}
+
+// Per-book lending terms, unset fields fall back to the contract-wide configuration
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct LendingTerms {
+    /// Overrides the default loan period
+    pub loan_period: Option<Duration>,
+    /// Who can borrow the book
+    #[serde(default)]
+    pub policy: LendingPolicy,
+    /// Fee paid to the owner for each loan, in a native denom
+    #[serde(default)]
+    pub price: Option<Coin>,
+    /// Refundable collateral locked by the borrower for the duration of a loan
+    #[serde(default)]
+    pub deposit: Option<Coin>,
+    /// Overrides the contract-wide late fee
+    #[serde(default)]
+    pub late_fee: Option<LateFee>,
+}
+
+// Fee charged for returning a book late. It accrues in the unit of the loan's due date:
+// per `per` seconds for time-based loans and per `per` blocks for height-based ones,
+// so `per` must count the unit of the default loan period.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LateFee {
+    /// Amount charged for every full or partial `per` the book is overdue
+    pub amount: Coin,
+    pub per: Duration,
+}
+
+// Who can borrow a book
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LendingPolicy {
+    /// Anyone can borrow the book
+    #[default]
+    Open,
+    /// Borrowing creates a request the owner has to accept
+    ApprovalRequired,
+    /// Only holders of the member role can borrow the book
+    MembersOnly,
+    /// The book cannot be borrowed
+    Closed,
+}
+
+impl LendingPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LendingPolicy::Open => "open",
+            LendingPolicy::ApprovalRequired => "approval_required",
+            LendingPolicy::MembersOnly => "members_only",
+            LendingPolicy::Closed => "closed",
+        }
+    }
 }
 
 // Approval structure with expiration
@@ -47,6 +296,208 @@ pub struct Approval {
     pub expires: Expiration,
 }
 
-// Legacy maps - maintained for backward compatibility
-pub const BORROWERS: Map<&str, Addr> = Map::new("borrowers");
-pub const BOOKS: Map<&str, Book> = Map::new("books");
\ No newline at end of file
+// Current loan of each book, keyed by token id and indexed by borrower
+pub struct LoanIndexes<'a> {
+    pub borrower: MultiIndex<'a, Addr, Loan, String>,
+}
+
+impl<'a> IndexList<Loan> for LoanIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Loan>> + '_> {
+        let v: Vec<&dyn Index<Loan>> = vec![&self.borrower];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn loans<'a>() -> IndexedMap<'a, &'a str, Loan, LoanIndexes<'a>> {
+    let indexes = LoanIndexes {
+        borrower: MultiIndex::new(
+            |_pk, l| l.borrower.clone(),
+            "borrowers",
+            "borrowers__borrower",
+        ),
+    };
+    IndexedMap::new("borrowers", indexes)
+}
+// Delegates allowed to borrow on behalf of a borrower, keyed by (borrower, delegate)
+pub const BORROW_DELEGATES: Map<(&Addr, &Addr), Expiration> = Map::new("borrow_delegates");
+// Pending requests to borrow approval-required books, keyed by request id
+pub const BORROW_REQUEST_SEQ: Item<u64> = Item::new("borrow_request_seq");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BorrowRequest {
+    pub token_id: String,
+    pub requester: Addr,
+    /// Book owner when the request was made
+    pub owner: Addr,
+    pub created_at: Timestamp,
+    /// Borrow fee held by the contract until the request is settled
+    #[serde(default)]
+    pub payment: Option<Coin>,
+    /// Deposit held by the contract until the request is settled
+    #[serde(default)]
+    pub deposit: Option<Coin>,
+}
+
+pub struct BorrowRequestIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, BorrowRequest, u64>,
+    pub requester: MultiIndex<'a, Addr, BorrowRequest, u64>,
+}
+
+impl<'a> IndexList<BorrowRequest> for BorrowRequestIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<BorrowRequest>> + '_> {
+        let v: Vec<&dyn Index<BorrowRequest>> = vec![&self.owner, &self.requester];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn borrow_requests<'a>() -> IndexedMap<'a, u64, BorrowRequest, BorrowRequestIndexes<'a>> {
+    let indexes = BorrowRequestIndexes {
+        owner: MultiIndex::new(
+            |_pk, r| r.owner.clone(),
+            "borrow_requests",
+            "borrow_requests__owner",
+        ),
+        requester: MultiIndex::new(
+            |_pk, r| r.requester.clone(),
+            "borrow_requests",
+            "borrow_requests__requester",
+        ),
+    };
+    IndexedMap::new("borrow_requests", indexes)
+}
+
+// Total deposits held in escrow for open loans and pending requests, keyed by denom
+pub const ESCROWED_DEPOSITS: Map<&str, Uint128> = Map::new("escrowed_deposits");
+
+// Platform fees collected by the contract and not yet withdrawn, keyed by denom
+pub const TREASURY: Map<&str, Uint128> = Map::new("treasury");
+
+// Total borrow fees paid with pending requests, keyed by denom
+pub const PENDING_PAYMENTS: Map<&str, Uint128> = Map::new("pending_payments");
+
+/// Funds of a denom the contract holds on behalf of borrowers: deposits in escrow and
+/// borrow fees of pending requests. They are not part of the treasury.
+pub fn held_for_borrowers(storage: &dyn Storage, denom: &str) -> StdResult<Uint128> {
+    let escrowed = ESCROWED_DEPOSITS
+        .may_load(storage, denom)?
+        .unwrap_or_default();
+    let pending = PENDING_PAYMENTS
+        .may_load(storage, denom)?
+        .unwrap_or_default();
+    Ok(escrowed + pending)
+}
+
+// Platform fee taken from borrow fees
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct FeeConfig {
+    /// Share of every borrow fee kept by the contract treasury, in basis points
+    pub platform_fee_bps: u16,
+    /// Late fee for books without their own
+    #[serde(default)]
+    pub late_fee: Option<LateFee>,
+}
+
+// CW20 token contracts accepted as payment, their tokens use the `cw20:<address>` denom
+pub const ACCEPTED_CW20: Map<&Addr, Empty> = Map::new("accepted_cw20");
+
+// Late fees left unpaid at return, keyed by borrower and book. Borrowers can't take out
+// new loans until they are paid.
+pub const OUTSTANDING_FEES: Map<(&Addr, &str), OutstandingFee> = Map::new("outstanding_fees");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutstandingFee {
+    /// Book owner the fee is owed to
+    pub owner: Addr,
+    pub amount: Coin,
+    pub incurred_at: Timestamp,
+}
+
+// Contract-wide lending configuration
+pub const LOAN_CONFIG: Item<LoanConfig> = Item::new("loan_config");
+
+// A book checked out by a borrower
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Loan {
+    pub borrower: Addr,
+    pub start_time: Timestamp,
+    pub start_height: u64,
+    pub due: Expiration,
+    /// Number of times the loan has been renewed
+    #[serde(default)]
+    pub renewals: u32,
+    /// Collateral held in escrow until the book is returned
+    #[serde(default)]
+    pub deposit: Option<Coin>,
+    /// Late fee in force when the book was borrowed, later changes don't apply to the loan
+    #[serde(default)]
+    pub late_fee: Option<LateFee>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LoanConfig {
+    /// Loan period used for books without their own
+    pub default_loan_period: Duration,
+    /// Maximum number of times a loan can be renewed
+    #[serde(default = "default_max_renewals")]
+    pub max_renewals: u32,
+    /// How long a returned book stays reserved for the first holder in line
+    #[serde(default = "default_pickup_window")]
+    pub pickup_window: Duration,
+    /// How long after the due date the owner has to wait before claiming the deposit
+    #[serde(default = "default_deposit_grace_period")]
+    pub deposit_grace_period: Duration,
+    /// Whether books on loan can change owner, the loan then carries on under the new owner
+    #[serde(default)]
+    pub transfer_loans: bool,
+}
+
+fn default_max_renewals() -> u32 {
+    2
+}
+
+fn default_pickup_window() -> Duration {
+    // Three days
+    Duration::Time(3 * 24 * 60 * 60)
+}
+
+fn default_deposit_grace_period() -> Duration {
+    // One week
+    Duration::Time(7 * 24 * 60 * 60)
+}
+
+impl Default for LoanConfig {
+    fn default() -> Self {
+        LoanConfig {
+            // Two weeks
+            default_loan_period: Duration::Time(14 * 24 * 60 * 60),
+            max_renewals: default_max_renewals(),
+            pickup_window: default_pickup_window(),
+            deposit_grace_period: default_deposit_grace_period(),
+            transfer_loans: false,
+        }
+    }
+}
+// FIFO queue of members waiting for each book, keyed by token id.
+// The queue is loaded and rewritten on every borrow and return, so it is kept short
+pub const HOLDS: Map<&str, Vec<Hold>> = Map::new("holds");
+pub const MAX_HOLDS: usize = 20;
+// Returned books set aside for the first holder in line, keyed by token id
+pub const RESERVATIONS: Map<&str, Reservation> = Map::new("reservations");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Hold {
+    pub holder: Addr,
+    pub placed_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Reservation {
+    pub holder: Addr,
+    /// End of the pickup window, the book passes to the next holder afterwards
+    pub expires: Expiration,
+}
+
+// Legacy book storage used before 0.3.0, only read when migrating into tokens
+pub const BOOKS: Map<&str, Book> = Map::new("books");
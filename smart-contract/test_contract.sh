@@ -80,7 +80,7 @@ echo "Checking book state before borrowing..."
 injectived query wasm contract-state smart $CONTRACT_ADDR '{"get_book": {"token_id": "test_book1"}}'
 
 echo "Attempting to borrow book..."
-echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "test_book1", "borrower": "'$WALLET_ADDR'"}}' \
+echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "test_book1"}}' \
     --from $FROM --gas auto --gas-adjustment 1.3 --fees $FEES --broadcast-mode sync -y
 check_status "Borrow book"
 wait_for_tx
@@ -111,7 +111,7 @@ check_status "Query all books"
 # Test 7: Try to borrow a non-existent book (should fail)
 print_separator "TEST 7: ATTEMPTING TO BORROW NON-EXISTENT BOOK"
 echo "Test 7: Attempting to borrow non-existent book..."
-RESPONSE=$(echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "non_existent_book", "borrower": "'$WALLET_ADDR'"}}' \
+RESPONSE=$(echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "non_existent_book"}}' \
     --from $WALLET_ADDR --gas auto --gas-adjustment 1.3 --fees $FEES --broadcast-mode sync -y)
 
 # Check if the response contains an error message or empty result
@@ -133,7 +133,7 @@ fi
 #wait_for_tx
 #
 #echo "Borrowing the second book..."
-#echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "test_book2", "borrower": "'$WALLET_ADDR'"}}' \
+#echo "$PASSPHRASE" | injectived tx wasm execute $CONTRACT_ADDR '{"borrow_book": {"token_id": "test_book2"}}' \
 #    --from $FROM --gas auto --gas-adjustment 1.3 --fees $FEES --broadcast-mode sync -y
 #check_status "Borrow second book"
 #wait_for_tx
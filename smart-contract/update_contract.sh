@@ -46,7 +46,10 @@ if echo "$ADMIN_INFO" | grep -q "admin: \"\""; then
     echo "Run redeploy_contract.sh to deploy a new instance of the contract."
 else
     echo "Contract has an admin. Migrating..."
-    echo "$PASSPHRASE" | injectived tx wasm migrate $CONTRACT_ADDR $NEW_CODE_ID '{}' \
+    # owner/minter are only used when upgrading from a version that did not store them,
+    # from_version when upgrading a 0.1.0 contract, whose stored version was overwritten (set FROM_VERSION to its version)
+    FROM_VERSION=${FROM_VERSION:-0.1.0}
+    echo "$PASSPHRASE" | injectived tx wasm migrate $CONTRACT_ADDR $NEW_CODE_ID '{"owner": "'$WALLET_ADDR'", "minter": "'$WALLET_ADDR'", "from_version": "'$FROM_VERSION'"}' \
         --from $FROM --gas auto --gas-adjustment 1.3 --fees $FEES -y
     
     echo "Waiting for migration to complete..."